@@ -1,29 +1,56 @@
-use std::{env::var, sync::LazyLock};
-use auth::{mock_verify, sign::sign, verify_passwd, Error as AuthError, Role::Sales, SalesData, Token};
+use std::{collections::HashMap, env::var, hash::Hash, sync::LazyLock};
+use auth::{hash_passwd, load_role_data, mock_verify, refresh, sign::keyring, verify_passwd, TimeDelta, DEFAULT_REFRESH_TTL, DEFAULT_TTL, Error as AuthError, Role::{self, Admin, Driver, Sales}, CustomerData, DriverData, SalesData, Token};
 use http_body_util::BodyExt as _;
-use hyper::{body::Body as _, header::SET_COOKIE, StatusCode};
+use rand::{rngs::OsRng, Rng as _};
+use hyper::{header::{ALLOW, SET_COOKIE}, StatusCode};
 use serde::Serialize;
 use serde_json::Value;
 use sql::*;
 use sqlx::{postgres::PgRow, prelude::*, PgConnection};
-use http_core::*;
+use http_core::{*, middleware::role};
 use sqlx::PgPool;
 use tokio::task::spawn_blocking;
-use types::{Deserialize, Destination, OrderId, Orders, Package, UserAnon, UserSid, Users};
+use types::{validate_metadata, CustomerAddresses, FieldError, Date, Deserialize, Destination, Employees, ManifestId, Manifests, OrderId, Orders, Package, Quote, RegionInput, Regions, Resi, Service, SessionId, Sessions, Status, TariffInput, Tariffs, TracingId, Tracings, UserAnon, UserId, UserSid, Users, Warehouses, WhId, WhSid, WhType};
 
 /// 64kb
 const MAX_PAYLOAD: u64 = 1024 * 64;
-const JWT_SECRET: LazyLock<String> = LazyLock::new(||var("JWT_SECRET").expect("checked"));
-const BASE: &str = "";
+/// 16mb, csv import
+const MAX_IMPORT_PAYLOAD: u64 = 1024 * 1024 * 16;
+/// token lifetime in seconds
+static TOKEN_TTL: LazyLock<TimeDelta> = LazyLock::new(||var("TOKEN_TTL").ok()
+    .and_then(|ttl|ttl.parse().ok()).map(TimeDelta::seconds).unwrap_or(DEFAULT_TTL));
+/// refresh token lifetime in seconds
+static REFRESH_TTL: LazyLock<TimeDelta> = LazyLock::new(||var("REFRESH_TTL").ok()
+    .and_then(|ttl|ttl.parse().ok()).map(TimeDelta::seconds).unwrap_or(DEFAULT_REFRESH_TTL));
+const RESI_RETRY: usize = 3;
+/// minimal region reference, full dataset loaded with `POST /admin/regions/import`
+const BUNDLED_REGIONS: &str = include_str!("../../data/regions.csv");
+/// rows per `UPSERT_REGIONS` statement
+const REGION_CHUNK: usize = 1000;
+/// minimum characters for region search
+const REGION_QUERY_MIN: usize = 2;
+
+type Req = http_core::Req<PgPool>;
+
+static ROUTER: LazyLock<Router<PgPool>> = LazyLock::new(routes);
 
 pub async fn handle(request: Request, state: PgPool) -> Response {
-    let (parts, body) = request.into_parts();
-    match router(&parts, body, &state).await {
+    match ROUTER.handle(request, state).await {
         Ok(ok) => ok,
-        Err(err) => Response::builder()
-            .status(err.status())
-            .json(json!{{ "error": err.error(), "message": err_msg(err) }})
-            .expect(concat!("deez ",line!())),
+        Err(err) => {
+            let mut body = json!{{ "error": err.error() }};
+            if let Some(fields) = err.fields() {
+                body["fields"] = json!(fields);
+            }
+            let status = err.status();
+            let allow = err.allow();
+            body["message"] = err_msg(err).into();
+            let mut response = Response::builder().status(status);
+            if let Some(allow) = allow {
+                response = response.header(ALLOW, allow);
+            }
+            response.json(body).expect(concat!("deez ",line!()))
+        },
     }
 }
 
@@ -34,112 +61,987 @@ fn err_msg(err: Error) -> String {
     }
 }
 
-pub async fn router(parts: &Parts, body: Body, state: &PgPool) -> Result {
-    if &parts.method != GET && body.size_hint().upper().unwrap_or(u64::MAX) > MAX_PAYLOAD  {
-        return Err(Error::Http(StatusCode::PAYLOAD_TOO_LARGE));
+pub fn routes() -> Router<PgPool> {
+    Router::new()
+        .body_limit(MAX_PAYLOAD)
+        .route("/", get(index))
+        .route("/login", post(login))
+        .route("/logout", get(logout))
+        .route("/auth", get(current_session))
+        .route("/auth/refresh", post(refresh_session))
+        .route("/auth/sessions", get(list_sessions))
+        .route("/auth/sessions/{id}", delete(revoke_session))
+        .route("/track/{resi}", get(track))
+        .nest("/orders", Router::new()
+            .route("/", get(list_orders))
+            .route("/tracings", get(list_order_tracings)))
+        .nest("/sales", Router::new()
+            .route("/", get(sales_orders).post(sales_create_order))
+            .route("/manifests", post(sales_gateway_out))
+            .route("/manifests/{id}", post(sales_gateway_in))
+            .route("/quotes", post(sales_quote))
+            .route("/completions", post(sales_complete))
+            .route("/regions", get(sales_regions)))
+        .nest("/driver", Router::new()
+            .route("/manifests", get(driver_manifests))
+            .route("/manifests/{id}", get(driver_manifest))
+            .route("/completions", post(driver_complete)))
+        .nest("/customer", Router::new()
+            .route("/orders", get(customer_orders))
+            .route("/orders/{resi}", get(customer_order))
+            .route("/addresses", get(customer_addresses).post(customer_create_address))
+            .route("/addresses/{id}", delete(customer_delete_address)))
+        .nest("/admin", Router::new()
+            .route("/users", get(admin_users).post(admin_create_user))
+            .route("/users/{id}", get(admin_user).patch(admin_update_user).delete(admin_deactivate_user))
+            .route("/warehouses", get(admin_warehouses).post(admin_create_wh))
+            .route("/warehouses/{id}", get(admin_wh).patch(admin_update_wh).delete(admin_deactivate_wh))
+            .route("/employees/{user_id}", get(admin_employees).put(admin_assign_employee).delete(admin_unassign_employee))
+            .route("/tracings", get(admin_tracings))
+            .route("/tariffs", get(admin_tariffs).put(admin_upsert_tariff))
+            .route("/tariffs/import", post(admin_import_tariffs).body_limit(MAX_IMPORT_PAYLOAD))
+            .route("/regions/import", post(admin_import_regions).body_limit(MAX_IMPORT_PAYLOAD))
+            .route("/regions/seed", post(admin_seed_regions))
+            .guard(role(Admin)))
+}
+
+async fn index(Req { state, .. }: Req) -> Result {
+    let us = sqlx::query(sql::SELECT_USERS)
+        .map(|e: PgRow|e.get::<String, _>("name"))
+        .fetch_all(&state).await.fatal()?;
+    us.into_response()
+}
+
+const SECURE: &str = if cfg!(debug_assertions) { "" } else { "; Secure" };
+const REFRESH_KEY: &str = "refresh_token";
+const LOGOUT_COOKIE: &str = if cfg!(debug_assertions) {
+    "access_token=; Path=/; Expires=Fri, 1 Jan 2010 00:00:00 UTC; HttpOnly; SameSite=None"
+} else {
+    "access_token=; Path=/; Expires=Fri, 1 Jan 2010 00:00:00 UTC; HttpOnly; SameSite=None; Secure"
+};
+const LOGOUT_REFRESH_COOKIE: &str = if cfg!(debug_assertions) {
+    "refresh_token=; Path=/; Expires=Fri, 1 Jan 2010 00:00:00 UTC; HttpOnly; SameSite=None"
+} else {
+    "refresh_token=; Path=/; Expires=Fri, 1 Jan 2010 00:00:00 UTC; HttpOnly; SameSite=None; Secure"
+};
+
+async fn login(Req { body, state, .. }: Req) -> Result {
+    #[derive(Serialize, Deserialize)]
+    struct Login {
+        phone: String,
+        password: String
     }
 
-    let path = parts.normalize_path();
+    let login = serde_json::from_slice::<Login>(&body.collect().await?.to_bytes()).bad_request()?;
+
+    let Some(user) = sqlx::query_as::<_, Users>(sql::FIND_USERS_BY_PHONE)
+        .bind(&login.phone).fetch_optional(&state).await.fatal()? else
+    {
+        let _ = spawn_blocking(move ||mock_verify(&login.password)).await.fatal()?;
+        return Err(Error::Auth(AuthError::InvalidCredential));
+    };
+
+    let hashed = user.password.clone();
 
-    if path == "/login" ||
-        path == "/logout" ||
-        path.starts_with("/auth") {
-        return handle_auth(parts, body, state).await;
+    if spawn_blocking(move ||verify_passwd(&login.password, &hashed))
+        .await.fatal()?.map_err(|e|Error::InternalError(e.to_string()))?.is_none()
+    {
+        return Err(Error::Auth(AuthError::InvalidCredential));
+    };
+
+    if user.deactivated_at.is_some() {
+        return Err(Error::Auth(AuthError::Deactivated));
     }
 
-    if path.starts_with("/orders") {
-        return handle_orders(parts, body, state).await;
+    let Some(role_data) = load_role_data(&user, &state).await.fatal()? else {
+        return Err(Error::Auth(AuthError::NoRoleData));
+    };
+
+    let mut tx = state.begin().await.fatal()?;
+    let session_id = sqlx::query_scalar::<_, SessionId>(CREATE_SESSIONS)
+        .bind(user.user_id).fetch_one(&mut *tx).await.fatal()?;
+    let refresh_token = issue_refresh(session_id, &mut tx).await?;
+    tx.commit().await.fatal()?;
+
+    session_response(Token::new(user, role_data, *TOKEN_TTL), &refresh_token)
+}
+
+async fn logout(Req { parts, state, .. }: Req) -> Result {
+    if let Some(refresh_token) = parts.get_cookie(REFRESH_KEY) {
+        sqlx::query(REVOKE_SESSIONS_BY_TOKEN)
+            .bind(refresh::hash(refresh_token)).execute(&state).await.fatal()?;
     }
+    Response::builder()
+        .header(SET_COOKIE, LOGOUT_COOKIE)
+        .header(SET_COOKIE, LOGOUT_REFRESH_COOKIE)
+        .empty()
+}
 
-    if path.starts_with("/sales") {
-        return handle_sales(parts, body, state).await;
+async fn current_session(_: Req, session: Token) -> Result {
+    session.into_response()
+}
+
+async fn list_sessions(Req { parts, state, .. }: Req, (session, page): (Token, Pagination)) -> Result {
+    let total = sqlx::query_scalar::<_, i64>(COUNT_SESSIONS_BY_USER_ID)
+        .bind(session.user_id).fetch_one(&state).await.fatal()?;
+    let data = sqlx::query_as::<_, Sessions>(SELECT_SESSIONS_BY_USER_ID)
+        .bind(session.user_id).bind(page.limit()).bind(page.offset())
+        .fetch_all(&state).await.fatal()?;
+    page.respond(&parts.uri, total, data)
+}
+
+async fn revoke_session(Req { state, .. }: Req, (Path(id), session): (Path<SessionId>, Token)) -> Result {
+    match sqlx::query_scalar::<_, SessionId>(REVOKE_SESSIONS_BY_USER_ID)
+        .bind(id).bind(session.user_id).fetch_optional(&state).await.fatal()?
+    {
+        Some(_) => Response::builder().status(StatusCode::NO_CONTENT).empty(),
+        None => NOT_FOUND,
     }
+}
 
-    match (&parts.method, path) {
-        (GET, "/") => {
-            let us = sqlx::query(sql::SELECT_USERS)
-                .map(|e: PgRow|e.get::<String, _>("name"))
-                .fetch_all(state).await.fatal()?;
-            us.into_response()
-        }
-        _ => NOT_FOUND,
+/// rotate refresh token, reusing an already used token revoke the whole session
+async fn refresh_session(Req { parts, body, state, .. }: Req) -> Result {
+    #[derive(Deserialize)]
+    struct Refresh {
+        refresh_token: String,
+    }
+
+    let refresh_token = match parts.get_cookie(REFRESH_KEY) {
+        Some(token) => token.to_owned(),
+        None => body.json::<Refresh>().await?.refresh_token,
+    };
+    let hash = refresh::hash(&refresh_token);
+
+    let mut tx = state.begin().await.fatal()?;
+
+    let Some((session_id, user_id, used, revoked, expired)) = sqlx::query_as::<_, (SessionId, UserId, bool, bool, bool)>(FIND_REFRESH_TOKENS)
+        .bind(&hash).fetch_optional(&mut *tx).await.fatal()? else
+    {
+        return UNAUTHORIZED;
+    };
+
+    if revoked {
+        return Err(Error::Auth(AuthError::InvalidToken));
+    }
+
+    if used {
+        sqlx::query(REVOKE_SESSIONS).bind(session_id).execute(&mut *tx).await.fatal()?;
+        tx.commit().await.fatal()?;
+        return Err(Error::Auth(AuthError::TokenReused));
+    }
+
+    if expired {
+        return Err(Error::Auth(AuthError::TokenExpired));
+    }
+
+    sqlx::query(USE_REFRESH_TOKENS).bind(&hash).execute(&mut *tx).await.fatal()?;
+    sqlx::query(REFRESH_SESSIONS).bind(session_id).execute(&mut *tx).await.fatal()?;
+
+    let Some(user) = sqlx::query_as::<_, Users>(FIND_USERS)
+        .bind(user_id).fetch_optional(&mut *tx).await.fatal()? else
+    {
+        return UNAUTHORIZED;
+    };
+
+    if user.deactivated_at.is_some() {
+        return Err(Error::Auth(AuthError::Deactivated));
+    }
+
+    let Some(role_data) = load_role_data(&user, &state).await.fatal()? else {
+        return Err(Error::Auth(AuthError::NoRoleData));
+    };
+
+    let refresh_token = issue_refresh(session_id, &mut tx).await?;
+
+    tx.commit().await.fatal()?;
+
+    session_response(Token::new(user, role_data, *TOKEN_TTL), &refresh_token)
+}
+
+async fn issue_refresh(session_id: SessionId, state: &mut PgConnection) -> Result<String> {
+    let refresh_token = refresh::generate();
+    sqlx::query(INSERT_REFRESH_TOKENS)
+        .bind(refresh::hash(&refresh_token)).bind(session_id)
+        .bind(REFRESH_TTL.num_seconds() as f64)
+        .execute(state).await.fatal()?;
+    Ok(refresh_token)
+}
+
+fn session_response(token: Token, refresh_token: &str) -> Result {
+    let token_str = keyring().sign(&serde_json::to_string(&token).expect("deez"));
+    let cookie = format!("access_token={token_str}; Path=/; Max-Age={}; HttpOnly; SameSite=None{SECURE}", token.max_age());
+    let refresh_cookie = format!("{REFRESH_KEY}={refresh_token}; Path=/; Max-Age={}; HttpOnly; SameSite=None{SECURE}", REFRESH_TTL.num_seconds());
+
+    Response::builder()
+        .header(SET_COOKIE, cookie)
+        .header(SET_COOKIE, refresh_cookie)
+        .json(token)
+}
+
+#[derive(Serialize)]
+struct Tracking {
+    resi: Resi,
+    status: Option<&'static str>,
+    sender: TrackingUser,
+    receiver: TrackingUser,
+    destination: TrackingDestination,
+    timeline: Vec<TrackingStep>,
+}
+
+#[derive(Serialize)]
+struct TrackingUser {
+    name: String,
+    phone: String,
+}
+
+#[derive(Serialize)]
+struct TrackingDestination {
+    kecamatan: String,
+    kabupaten: String,
+    provinsi: String,
+}
+
+#[derive(Serialize)]
+struct TrackingStep {
+    status: Status,
+    step: &'static str,
+    subject: Option<String>,
+    warehouse: Option<String>,
+    traced_at: Date,
+}
+
+/// public tracking, only display names and masked phone is exposed
+async fn track_order(resi: &Resi, state: &PgPool) -> Result<Tracking> {
+    let Some((id, destination, sender, sender_phone, receiver, receiver_phone)) =
+        sqlx::query_as::<_, (OrderId, String, String, String, String, String)>(FIND_ORDER_TRACKING)
+        .bind(resi.as_str()).fetch_optional(state).await.fatal()? else
+    {
+        return Err(Error::Http(StatusCode::NOT_FOUND));
+    };
+
+    let destination = serde_json::from_str::<Destination>(&destination).fatal()?;
+
+    let timeline = sqlx::query_as::<_, (Status, Date, Option<String>, Option<String>)>(SELECT_TRACKING_BY_ORDER_ID)
+        .bind(id).fetch_all(state).await.fatal()?
+        .into_iter()
+        .map(|(status, traced_at, subject, warehouse)|TrackingStep {
+            step: status.step(), status, subject, warehouse, traced_at,
+        })
+        .collect::<Vec<_>>();
+
+    Ok(Tracking {
+        resi: resi.clone(),
+        status: timeline.last().map(|e|e.step),
+        sender: TrackingUser { name: sender, phone: mask_phone(&sender_phone) },
+        receiver: TrackingUser { name: receiver, phone: mask_phone(&receiver_phone) },
+        destination: TrackingDestination {
+            kecamatan: destination.kecamatan,
+            kabupaten: destination.kabupaten,
+            provinsi: destination.provinsi,
+        },
+        timeline,
+    })
+}
+
+/// keep first 4 and last 2 characters, `081234567890` become `0812******90`
+fn mask_phone(phone: &str) -> String {
+    let chars = phone.chars().collect::<Vec<_>>();
+    if chars.len() <= 6 {
+        return "*".repeat(chars.len());
+    }
+    chars.iter().enumerate()
+        .map(|(i,c)|if i < 4 || i >= chars.len() - 2 { *c } else { '*' })
+        .collect()
+}
+
+async fn track(Req { state, .. }: Req, Path(resi): Path<Resi>) -> Result {
+    track_order(&resi, &state).await?.into_response()
+}
+
+async fn list_orders(Req { parts, state, .. }: Req, page: CursorPagination) -> Result {
+    if let Paging::Cursor(after) = page.paging::<OrderId>(&parts.uri)? {
+        let data = sqlx::query_as::<_, Orders>(SELECT_ORDERS_AFTER)
+            .bind(after).bind(page.limit()).fetch_all(&state).await.fatal()?;
+        return page.respond_cursor(&parts.uri, data, |e|e.order_id);
     }
+
+    let total = sqlx::query_scalar::<_, i64>(COUNT_ORDERS).fetch_one(&state).await.fatal()?;
+    let data = sqlx::query_as::<_, Orders>(SELECT_ORDERS)
+        .bind(page.limit()).bind(page.offset()).fetch_all(&state).await.fatal()?;
+    page.respond(&parts.uri, total, data)
+}
+
+async fn list_order_tracings(Req { parts, state, .. }: Req, page: CursorPagination) -> Result {
+    if let Paging::Cursor(after) = page.paging::<OrderId>(&parts.uri)? {
+        let data = sqlx::query_as::<_, Orders>(SELECT_ORDERS_TRACINGS_AFTER)
+            .bind(after).bind(page.limit()).fetch_all(&state).await.fatal()?;
+        return page.respond_cursor(&parts.uri, data, |e|e.order_id);
+    }
+
+    let total = sqlx::query_scalar::<_, i64>(COUNT_ORDERS_TRACINGS).fetch_one(&state).await.fatal()?;
+    let data = sqlx::query_as::<_, Orders>(SELECT_ORDERS_TRACINGS)
+        .bind(page.limit()).bind(page.offset()).fetch_all(&state).await.fatal()?;
+    page.respond(&parts.uri, total, data)
 }
 
-async fn handle_auth(parts: &Parts, body: Body, state: &PgPool) -> Result {
-    const SECURE: &str = if cfg!(debug_assertions) { "" } else { "; Secure" };
-    const LOGOUT_COOKIE: &str = if cfg!(debug_assertions) {
-        "access_token=; Path=/; Expires=Fri, 1 Jan 2010 00:00:00 UTC; HttpOnly; SameSite=None"
-    } else {
-        "access_token=; Path=/; Expires=Fri, 1 Jan 2010 00:00:00 UTC; HttpOnly; SameSite=None; Secure"
+async fn sales_orders(Req { parts, state, .. }: Req, (Session(_, sales), page): (Session<SalesData>, CursorPagination)) -> Result {
+    if let Paging::Cursor(before) = page.paging::<OrderId>(&parts.uri)? {
+        let data = sqlx::query_as::<_, Orders>(SELECT_ORDERS_BY_WH_ID_BEFORE)
+            .bind(sales.wh_id).bind(before).bind(page.limit()).fetch_all(&state).await.fatal()?;
+        return page.respond_cursor(&parts.uri, data, |e|e.order_id);
+    }
+
+    let total = sqlx::query_scalar::<_, i64>(COUNT_ORDERS_BY_WH_ID)
+        .bind(sales.wh_id).fetch_one(&state).await.fatal()?;
+    let data = sqlx::query_as::<_, Orders>(SELECT_ORDERS_BY_WH_ID)
+        .bind(sales.wh_id).bind(page.limit()).bind(page.offset()).fetch_all(&state).await.fatal()?;
+    page.respond(&parts.uri, total, data)
+}
+
+async fn sales_create_order(Req { body, state, .. }: Req, Session(session, sales): Session<SalesData>) -> Result {
+    create_order(&session, &sales, &body.json().await?, &state).await?.into_response()
+}
+
+async fn sales_gateway_out(Req { body, state, .. }: Req, Session(session, sales): Session<SalesData>) -> Result {
+    gateway_out(&session, &sales, &body.json().await?, &state).await?.into_response()
+}
+
+async fn sales_gateway_in(Req { state, .. }: Req, (Path(id), Session(session, sales)): (Path<ManifestId>, Session<SalesData>)) -> Result {
+    gateway_in(&session, &sales, &id, &state).await?.into_response()
+}
+
+async fn sales_quote(Req { body, state, .. }: Req, Session(_, sales): Session<SalesData>) -> Result {
+    let data = body.json::<QuoteRequest>().await?;
+    quote(&sales.wh_id, &data.destination, data.service, &data.packages, &state).await?.into_response()
+}
+
+async fn sales_complete(Req { body, state, .. }: Req, Session(session, sales): Session<SalesData>) -> Result {
+    complete_sales(&session, &sales, &body.json().await?, &state).await?.into_response()
+}
+
+#[derive(Deserialize)]
+struct RegionQuery {
+    #[serde(default)]
+    q: String,
+}
+
+async fn sales_regions(Req { parts, state, .. }: Req, (_, Query(query), page): (Session<SalesData>, Query<RegionQuery>, Pagination)) -> Result {
+    let (total, data) = search_regions(&query.q, page, &state).await?;
+    page.respond(&parts.uri, total, data)
+}
+
+async fn driver_manifests(Req { parts, state, .. }: Req, (Session(session, _), page): (Session<DriverData>, Pagination)) -> Result {
+    let total = sqlx::query_scalar::<_, i64>(COUNT_MANIFESTS_BY_DRIVER)
+        .bind(session.user_id).fetch_one(&state).await.fatal()?;
+    let data = sqlx::query_as::<_, Manifests>(SELECT_MANIFESTS_BY_DRIVER)
+        .bind(session.user_id).bind(page.limit()).bind(page.offset()).fetch_all(&state).await.fatal()?;
+    page.respond(&parts.uri, total, data)
+}
+
+#[derive(Serialize)]
+struct DriverManifest {
+    manifest: Manifests,
+    orders: Vec<Orders>,
+}
+
+async fn driver_manifest(Req { state, .. }: Req, (Path(id), Session(session, _)): (Path<ManifestId>, Session<DriverData>)) -> Result {
+    let Some(manifest) = sqlx::query_as::<_, Manifests>(FIND_MANIFESTS_BY_DRIVER)
+        .bind(id).bind(session.user_id).fetch_optional(&state).await.fatal()? else
+    {
+        return Err(Error::Logic(LogicError::ManifestIdNotFound(id.0)));
     };
 
-    let path = parts.normalize_path();
+    let orders = sqlx::query_as::<_, Orders>(SELECT_ORDERS_BY_MANIFEST_ID)
+        .bind(id).fetch_all(&state).await.fatal()?;
 
-    if &parts.method == POST && path == "/login" {
-        #[derive(Serialize, Deserialize)]
-        struct Login {
-            phone: String,
-            password: String
-        }
+    DriverManifest { manifest, orders }.into_response()
+}
 
-        let login = serde_json::from_slice::<Login>(&body.collect().await?.to_bytes()).bad_request()?;
+async fn driver_complete(Req { body, state, .. }: Req, Session(session, _): Session<DriverData>) -> Result {
+    complete_driver(&session, &body.json().await?, &state).await?.into_response()
+}
 
-        let Some(user) = sqlx::query_as::<_, Users>(sql::FIND_USERS_BY_PHONE)
-            .bind(&login.phone).fetch_optional(state).await.fatal()? else
-        {
-            let _ = spawn_blocking(move ||mock_verify(&login.password)).await.fatal()?;
-            return Err(Error::Auth(AuthError::InvalidCredential));
-        };
+async fn customer_orders(Req { parts, state, .. }: Req, (Session(session, _), page): (Session<CustomerData>, CursorPagination)) -> Result {
+    if let Paging::Cursor(before) = page.paging::<OrderId>(&parts.uri)? {
+        let data = sqlx::query_as::<_, Orders>(SELECT_ORDERS_BY_CUSTOMER_BEFORE)
+            .bind(session.user_id).bind(before).bind(page.limit()).fetch_all(&state).await.fatal()?;
+        return page.respond_cursor(&parts.uri, data, |e|e.order_id);
+    }
+
+    let total = sqlx::query_scalar::<_, i64>(COUNT_ORDERS_BY_CUSTOMER)
+        .bind(session.user_id).fetch_one(&state).await.fatal()?;
+    let data = sqlx::query_as::<_, Orders>(SELECT_ORDERS_BY_CUSTOMER)
+        .bind(session.user_id).bind(page.limit()).bind(page.offset()).fetch_all(&state).await.fatal()?;
+    page.respond(&parts.uri, total, data)
+}
+
+async fn customer_order(Req { state, .. }: Req, (Path(resi), Session(session, _)): (Path<Resi>, Session<CustomerData>)) -> Result {
+    match sqlx::query_scalar::<_, OrderId>(FIND_ORDERS_BY_CUSTOMER_RESI)
+        .bind(session.user_id).bind(resi.as_str()).fetch_optional(&state).await.fatal()?
+    {
+        Some(_) => track_order(&resi, &state).await?.into_response(),
+        None => NOT_FOUND,
+    }
+}
+
+async fn customer_addresses(Req { parts, state, .. }: Req, (Session(session, _), page): (Session<CustomerData>, Pagination)) -> Result {
+    let total = sqlx::query_scalar::<_, i64>(COUNT_CUSTOMER_ADDRESSES_BY_USER_ID)
+        .bind(session.user_id).fetch_one(&state).await.fatal()?;
+    let data = sqlx::query_as::<_, CustomerAddresses>(SELECT_CUSTOMER_ADDRESSES_BY_USER_ID)
+        .bind(session.user_id).bind(page.limit()).bind(page.offset()).fetch_all(&state).await.fatal()?;
+    page.respond(&parts.uri, total, data)
+}
+
+#[derive(Deserialize)]
+struct CreateAddress {
+    label: String,
+    destination: Destination,
+}
+
+async fn customer_create_address(Req { body, state, .. }: Req, Session(session, _): Session<CustomerData>) -> Result {
+    let data = body.json::<CreateAddress>().await?;
+    sqlx::query_as::<_, CustomerAddresses>(CREATE_CUSTOMER_ADDRESSES)
+        .bind(session.user_id).bind(&data.label).bind(data.destination.json_str()?)
+        .fetch_one(&state).await.fatal()?
+        .into_response()
+}
+
+async fn customer_delete_address(Req { state, .. }: Req, (Path(id), Session(session, _)): (Path<i32>, Session<CustomerData>)) -> Result {
+    match sqlx::query(DELETE_CUSTOMER_ADDRESSES_BY_USER_ID)
+        .bind(id).bind(session.user_id).execute(&state).await.fatal()?.rows_affected()
+    {
+        0 => NOT_FOUND,
+        _ => Response::builder().status(StatusCode::NO_CONTENT).empty(),
+    }
+}
+
+async fn admin_users(Req { parts, state, .. }: Req, page: Pagination) -> Result {
+    let total = sqlx::query_scalar::<_, i64>(COUNT_USERS).fetch_one(&state).await.fatal()?;
+    let data = sqlx::query_as::<_, Users>(SELECT_USERS)
+        .bind(page.limit()).bind(page.offset()).fetch_all(&state).await.fatal()?;
+    page.respond(&parts.uri, total, data)
+}
+
+async fn admin_create_user(Req { body, state, .. }: Req) -> Result {
+    create_user(body.json().await?, &state).await?.into_response()
+}
+
+async fn admin_user(Req { state, .. }: Req, Path(id): Path<UserId>) -> Result {
+    match sqlx::query_as::<_, Users>(FIND_USERS).bind(id).fetch_optional(&state).await.fatal()? {
+        Some(user) => user.into_response(),
+        None => NOT_FOUND,
+    }
+}
+
+async fn admin_update_user(Req { body, state, .. }: Req, Path(id): Path<UserId>) -> Result {
+    update_user(&id, body.json().await?, &state).await?.into_response()
+}
+
+async fn admin_deactivate_user(Req { state, .. }: Req, Path(id): Path<UserId>) -> Result {
+    deactivate_user(&id, &state).await?.into_response()
+}
+
+#[derive(Deserialize)]
+struct WhQuery {
+    wh_type: Option<String>,
+}
+
+async fn admin_warehouses(Req { parts, state, .. }: Req, (Query(query), page): (Query<WhQuery>, Pagination)) -> Result {
+    let (total, data) = match query.wh_type {
+        Some(wh_type) => {
+            let wh_type = parse_wh_type(&wh_type)?;
+            (sqlx::query_scalar::<_, i64>(COUNT_WAREHOUSES_BY_WH_TYPE)
+                .bind(wh_type.as_str()).fetch_one(&state).await.fatal()?,
+            sqlx::query_as::<_, Warehouses>(SELECT_WAREHOUSES_BY_WH_TYPE)
+                .bind(wh_type.as_str())
+                .bind(page.limit()).bind(page.offset()).fetch_all(&state).await.fatal()?)
+        },
+        None => (sqlx::query_scalar::<_, i64>(COUNT_WH).fetch_one(&state).await.fatal()?,
+            sqlx::query_as::<_, Warehouses>(SELECT_WH)
+                .bind(page.limit()).bind(page.offset()).fetch_all(&state).await.fatal()?),
+    };
+    page.respond(&parts.uri, total, data)
+}
+
+async fn admin_create_wh(Req { body, state, .. }: Req) -> Result {
+    create_wh(body.json().await?, &state).await?.into_response()
+}
+
+async fn admin_wh(Req { state, .. }: Req, Path(id): Path<WhId>) -> Result {
+    match sqlx::query_as::<_, Warehouses>(FIND_WH).bind(id).fetch_optional(&state).await.fatal()? {
+        Some(wh) => wh.into_response(),
+        None => NOT_FOUND,
+    }
+}
+
+async fn admin_update_wh(Req { body, state, .. }: Req, Path(id): Path<WhId>) -> Result {
+    update_wh(&id, body.json().await?, &state).await?.into_response()
+}
+
+async fn admin_deactivate_wh(Req { state, .. }: Req, Path(id): Path<WhId>) -> Result {
+    deactivate_wh(&id, &state).await?.into_response()
+}
+
+async fn admin_employees(Req { parts, state, .. }: Req, (Path(id), page): (Path<UserId>, Pagination)) -> Result {
+    let total = sqlx::query_scalar::<_, i64>(COUNT_EMPLOYEES_BY_USER_ID)
+        .bind(id).fetch_one(&state).await.fatal()?;
+    let data = sqlx::query_as::<_, Employees>(SELECT_EMPLOYEES_BY_USER_ID)
+        .bind(id).bind(page.limit()).bind(page.offset()).fetch_all(&state).await.fatal()?;
+    page.respond(&parts.uri, total, data)
+}
+
+async fn admin_assign_employee(Req { body, state, .. }: Req, Path(id): Path<UserId>) -> Result {
+    assign_employee(&id, body.json().await?, &state).await?.into_response()
+}
+
+async fn admin_unassign_employee(Req { state, .. }: Req, Path(id): Path<UserId>) -> Result {
+    unassign_employee(&id, &state).await?.into_response()
+}
+
+#[derive(Deserialize)]
+struct TariffQuery {
+    wh_id: Option<WhId>,
+}
+
+/// newest first, keyset on `(traced_at, tracing_id)`
+async fn admin_tracings(Req { parts, state, .. }: Req, page: CursorPagination) -> Result {
+    if let Paging::Cursor(before) = page.paging::<(Date, TracingId)>(&parts.uri)? {
+        let (traced_at, tracing_id) = before.unzip();
+        let data = sqlx::query_as::<_, Tracings>(SELECT_TRACINGS_BEFORE)
+            .bind(traced_at).bind(tracing_id).bind(page.limit()).fetch_all(&state).await.fatal()?;
+        return page.respond_cursor(&parts.uri, data, |e|(e.traced_at, e.tracing_id));
+    }
 
-        let hashed = user.password.clone();
+    let total = sqlx::query_scalar::<_, i64>(COUNT_TRACINGS).fetch_one(&state).await.fatal()?;
+    let data = sqlx::query_as::<_, Tracings>(SELECT_TRACINGS_LATEST)
+        .bind(page.limit()).bind(page.offset()).fetch_all(&state).await.fatal()?;
+    page.respond(&parts.uri, total, data)
+}
+
+async fn admin_tariffs(Req { parts, state, .. }: Req, (Query(query), page): (Query<TariffQuery>, Pagination)) -> Result {
+    let (total, data) = match query.wh_id {
+        Some(wh_id) => (sqlx::query_scalar::<_, i64>(COUNT_TARIFFS_BY_WH_ID)
+                .bind(wh_id).fetch_one(&state).await.fatal()?,
+            sqlx::query_as::<_, Tariffs>(SELECT_TARIFFS_BY_WH_ID)
+                .bind(wh_id).bind(page.limit()).bind(page.offset()).fetch_all(&state).await.fatal()?),
+        None => (sqlx::query_scalar::<_, i64>(COUNT_TARIFFS).fetch_one(&state).await.fatal()?,
+            sqlx::query_as::<_, Tariffs>(SELECT_TARIFFS)
+                .bind(page.limit()).bind(page.offset()).fetch_all(&state).await.fatal()?),
+    };
+    page.respond(&parts.uri, total, data)
+}
+
+async fn admin_upsert_tariff(Req { body, state, .. }: Req) -> Result {
+    let data = body.json::<TariffInput>().await?;
+    data.validate().map_err(Error::Validation)?;
+    let mut tx = state.begin().await.fatal()?;
+    let tariff = upsert_tariff(&data, &mut tx).await?;
+    tx.commit().await.fatal()?;
+    tariff.into_response()
+}
+
+async fn admin_import_tariffs(Req { body, state, .. }: Req) -> Result {
+    import_tariffs(&body.text().await?, &state).await?.into_response()
+}
+
+async fn admin_import_regions(Req { body, state, .. }: Req) -> Result {
+    import_regions(&body.text().await?, &state).await?.into_response()
+}
+
+async fn admin_seed_regions(Req { state, .. }: Req) -> Result {
+    import_regions(BUNDLED_REGIONS, &state).await?.into_response()
+}
+
+#[derive(Deserialize)]
+struct CreateUser {
+    name: String,
+    phone: String,
+    password: String,
+    role: Role,
+    metadata: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct UpdateUser {
+    name: Option<String>,
+    phone: Option<String>,
+    password: Option<String>,
+    role: Option<Role>,
+    metadata: Option<Value>,
+}
+
+async fn create_user(data: CreateUser, state: &PgPool) -> Result<Users> {
+    let metadata = data.metadata.unwrap_or_else(||json!({}));
+    validate_metadata(&data.role, &metadata).map_err(Error::Validation)?;
+
+    let password = data.password;
+    let hashed = spawn_blocking(move ||hash_passwd(&password))
+        .await.fatal()?.map_err(|e|Error::InternalError(e.to_string()))?;
+
+    let mut tx = state.begin().await.fatal()?;
+
+    let user = sqlx::query_as::<_, Users>(CREATE_USERS)
+        .bind(&data.name).bind(&data.phone).bind(hashed).bind(data.role.as_str())
+        .bind(metadata.json_str()?)
+        .fetch_one(&mut *tx).await.map_err(conflict)?;
+
+    sqlx::query(SNAPSHOT_USERS).bind(user.user_id).execute(&mut *tx).await.fatal()?;
+
+    tx.commit().await.fatal()?;
 
-        if spawn_blocking(move ||verify_passwd(&login.password, &hashed))
-            .await.fatal()?.map_err(|e|Error::InternalError(e.to_string()))?.is_none()
-        {
-            return Err(Error::Auth(AuthError::InvalidCredential));
+    Ok(user)
+}
+
+async fn update_user(id: &UserId, data: UpdateUser, state: &PgPool) -> Result<Users> {
+    let hashed = match data.password {
+        Some(password) => Some(spawn_blocking(move ||hash_passwd(&password))
+            .await.fatal()?.map_err(|e|Error::InternalError(e.to_string()))?),
+        None => None,
+    };
+    let metadata = match &data.metadata {
+        Some(metadata) => Some(metadata.json_str()?),
+        None => None,
+    };
+
+    let mut tx = state.begin().await.fatal()?;
+
+    // resulting row is validated, legacy driver metadata may not satisfy the schema
+    let Some(user) = sqlx::query_as::<_, Users>(FIND_USERS_FOR_UPDATE)
+        .bind(id).fetch_optional(&mut *tx).await.fatal()? else
+    {
+        return Err(Error::Logic(LogicError::UserIdNotFound(id.0)));
+    };
+    validate_metadata(
+        data.role.as_ref().unwrap_or(&user.role),
+        data.metadata.as_ref().unwrap_or(&user.metadata),
+    ).map_err(Error::Validation)?;
+
+    let Some(user) = sqlx::query_as::<_, Users>(UPDATE_USERS)
+        .bind(id).bind(&data.name).bind(&data.phone).bind(hashed)
+        .bind(data.role.as_ref().map(Role::as_str)).bind(metadata)
+        .fetch_optional(&mut *tx).await.map_err(conflict)? else
+    {
+        return Err(Error::Logic(LogicError::UserIdNotFound(id.0)));
+    };
+
+    sqlx::query(SNAPSHOT_USERS).bind(user.user_id).execute(&mut *tx).await.fatal()?;
+
+    tx.commit().await.fatal()?;
+
+    Ok(user)
+}
+
+async fn deactivate_user(id: &UserId, state: &PgPool) -> Result<Users> {
+    let mut tx = state.begin().await.fatal()?;
+
+    let Some(user) = sqlx::query_as::<_, Users>(DEACTIVATE_USERS)
+        .bind(id).fetch_optional(&mut *tx).await.map_err(conflict)? else
+    {
+        return Err(Error::Logic(LogicError::UserIdNotFound(id.0)));
+    };
+
+    sqlx::query(REVOKE_ALL_SESSIONS).bind(id).execute(&mut *tx).await.fatal()?;
+
+    tx.commit().await.fatal()?;
+
+    Ok(user)
+}
+
+#[derive(Deserialize)]
+struct CreateWh {
+    wh_name: String,
+    wh_type: WhType,
+}
+
+#[derive(Deserialize)]
+struct UpdateWh {
+    wh_name: Option<String>,
+    wh_type: Option<WhType>,
+}
+
+async fn create_wh(data: CreateWh, state: &PgPool) -> Result<Warehouses> {
+    let mut tx = state.begin().await.fatal()?;
+
+    let wh = sqlx::query_as::<_, Warehouses>(CREATE_WH)
+        .bind(&data.wh_name).bind(data.wh_type.as_str())
+        .fetch_one(&mut *tx).await.fatal()?;
+
+    sqlx::query(SNAPSHOT_WH).bind(wh.wh_id).execute(&mut *tx).await.fatal()?;
+
+    tx.commit().await.fatal()?;
+
+    Ok(wh)
+}
+
+async fn update_wh(id: &WhId, data: UpdateWh, state: &PgPool) -> Result<Warehouses> {
+    let mut tx = state.begin().await.fatal()?;
+
+    let Some(wh) = sqlx::query_as::<_, Warehouses>(UPDATE_WH)
+        .bind(id).bind(&data.wh_name).bind(data.wh_type.as_ref().map(WhType::as_str))
+        .fetch_optional(&mut *tx).await.fatal()? else
+    {
+        return Err(Error::Logic(LogicError::WhIdNotFound(id.0)));
+    };
+
+    sqlx::query(SNAPSHOT_WH).bind(wh.wh_id).execute(&mut *tx).await.fatal()?;
+
+    tx.commit().await.fatal()?;
+
+    Ok(wh)
+}
+
+async fn deactivate_wh(id: &WhId, state: &PgPool) -> Result<Warehouses> {
+    match sqlx::query_as::<_, Warehouses>(DEACTIVATE_WH).bind(id).fetch_optional(state).await.fatal()? {
+        Some(wh) => Ok(wh),
+        None => Err(Error::Logic(LogicError::WhIdNotFound(id.0))),
+    }
+}
+
+/// warehouse exists and is not deactivated, locked until transaction end
+async fn ensure_wh(id: &WhId, state: &mut PgConnection) -> Result<()> {
+    match sqlx::query_scalar::<_, bool>(LOCK_WH_ACTIVE).bind(id).fetch_optional(state).await.fatal()? {
+        Some(true) => Ok(()),
+        Some(false) => Err(Error::Logic(LogicError::WhDeactivated(id.0))),
+        None => Err(Error::Logic(LogicError::WhIdNotFound(id.0))),
+    }
+}
+
+#[derive(Deserialize)]
+struct AssignEmployee {
+    wh_id: WhId,
+}
+
+#[derive(Serialize)]
+struct Assignment {
+    previous: Option<Employees>,
+    current: Option<Employees>,
+}
+
+/// assign or transfer, previous assignment is ended
+async fn assign_employee(id: &UserId, data: AssignEmployee, state: &PgPool) -> Result<Assignment> {
+    let mut tx = state.begin().await.fatal()?;
+
+    lock_employee(id, &mut tx).await?;
+
+    let previous = sqlx::query_as::<_, Employees>(END_EMPLOYEES)
+        .bind(id).fetch_optional(&mut *tx).await.fatal()?;
+
+    if previous.as_ref().is_some_and(|e|e.wh_id == data.wh_id) {
+        return Err(Error::Logic(LogicError::SameWarehouse(data.wh_id.0)));
+    }
+
+    ensure_wh(&data.wh_id, &mut tx).await?;
+
+    let current = match sqlx::query_as::<_, Employees>(CREATE_EMPLOYEES)
+        .bind(id).bind(data.wh_id).fetch_one(&mut *tx).await
+    {
+        Ok(ok) => ok,
+        Err(err) if err.as_database_error().is_some_and(|e|e.is_foreign_key_violation()) => {
+            return Err(Error::Logic(LogicError::WhIdNotFound(data.wh_id.0)));
+        },
+        Err(err) => return Err(Error::InternalError(err.to_string())),
+    };
+
+    tx.commit().await.fatal()?;
+
+    Ok(Assignment { previous, current: Some(current) })
+}
+
+async fn unassign_employee(id: &UserId, state: &PgPool) -> Result<Assignment> {
+    let mut tx = state.begin().await.fatal()?;
+
+    lock_employee(id, &mut tx).await?;
+
+    let Some(previous) = sqlx::query_as::<_, Employees>(END_EMPLOYEES)
+        .bind(id).fetch_optional(&mut *tx).await.fatal()? else
+    {
+        return Err(Error::Logic(LogicError::NotAssigned(id.0)));
+    };
+
+    tx.commit().await.fatal()?;
+
+    Ok(Assignment { previous: Some(previous), current: None })
+}
+
+/// lock user row to serialize assignment, only sales and driver can be employee
+async fn lock_employee(id: &UserId, state: &mut PgConnection) -> Result<()> {
+    match sqlx::query_scalar::<_, Role>(LOCK_USERS_ROLE).bind(id).fetch_optional(state).await.fatal()? {
+        Some(Sales | Driver) => Ok(()),
+        Some(_) => Err(Error::Logic(LogicError::NotEmployee(id.0))),
+        None => Err(Error::Logic(LogicError::UserIdNotFound(id.0))),
+    }
+}
+
+async fn upsert_tariff(data: &TariffInput, state: &mut PgConnection) -> Result<Tariffs> {
+    let tariff = match sqlx::query_as::<_, Tariffs>(UPSERT_TARIFFS)
+        .bind(data.wh_id).bind(&data.provinsi).bind(&data.kabupaten).bind(data.service.as_str())
+        .bind(data.rate_per_kg).bind(data.min_charge).bind(data.eta_days).bind(data.effective_from)
+        .fetch_optional(state).await
+    {
+        Ok(ok) => ok,
+        Err(err) if err.as_database_error().is_some_and(|e|e.is_foreign_key_violation()) => {
+            return Err(Error::Logic(LogicError::WhIdNotFound(data.wh_id.0)));
+        },
+        Err(err) => return Err(Error::InternalError(err.to_string())),
+    };
+
+    tariff.ok_or_else(||Error::Logic(LogicError::TariffImmutable(format!("{}, {} ({}) {}",
+        data.kabupaten, data.provinsi, data.service.as_str(), data.effective_from))))
+}
+
+#[derive(Serialize)]
+struct TariffImport {
+    imported: usize,
+}
+
+/// csv with header of [`TariffInput::FIELDS`], nothing is imported if any row is invalid
+async fn import_tariffs(csv: &str, state: &PgPool) -> Result<TariffImport> {
+    let CsvRows { rows: tariffs, mut errors } = csv_rows(csv, &TariffInput::FIELDS, TariffInput::from_record, ("effective_from", TariffInput::key))?;
+
+    let wh_ids = tariffs.iter().map(|(_,e)|e.wh_id.0).collect::<Vec<_>>();
+    let found = sqlx::query_scalar::<_, i32>(SELECT_WH_IDS).bind(&wh_ids).fetch_all(state).await.fatal()?;
+    errors.extend(tariffs.iter()
+        .filter(|(_,e)|!found.contains(&e.wh_id.0))
+        .map(|(row,e)|row_error(*row, "wh_id", format!("Warehouse Id `{}` Not Found", e.wh_id.0))));
+
+    let immutable = sqlx::query_scalar::<_, i32>(SELECT_TARIFFS_IMMUTABLE)
+        .bind(tariffs.iter().map(|(row,_)|*row as i32).collect::<Vec<_>>()).bind(&wh_ids)
+        .bind(tariffs.iter().map(|(_,e)|e.provinsi.as_str()).collect::<Vec<_>>())
+        .bind(tariffs.iter().map(|(_,e)|e.kabupaten.as_str()).collect::<Vec<_>>())
+        .bind(tariffs.iter().map(|(_,e)|e.service.as_str()).collect::<Vec<_>>())
+        .bind(tariffs.iter().map(|(_,e)|e.effective_from).collect::<Vec<_>>())
+        .fetch_all(state).await.fatal()?;
+    errors.extend(immutable.into_iter()
+        .map(|row|row_error(row as usize, "effective_from", "tariff version is already effective")));
+
+    if !errors.is_empty() {
+        errors.sort_by_cached_key(|e|e.field.split(['[',']']).nth(1).and_then(|e|e.parse::<usize>().ok()));
+        return Err(Error::Validation(errors));
+    }
+
+    let mut tx = state.begin().await.fatal()?;
+    for (row, tariff) in &tariffs {
+        // version may become effective since checked
+        match upsert_tariff(tariff, &mut tx).await {
+            Err(Error::Logic(LogicError::TariffImmutable(_))) => return Err(Error::Validation(vec![
+                row_error(*row, "effective_from", "tariff version is already effective"),
+            ])),
+            result => result?,
         };
+    }
+    tx.commit().await.fatal()?;
 
-        let token = Token::new(user, Value::Null);
-        let token_str = sign(&*JWT_SECRET, &serde_json::to_string(&token).expect("deez"));
-        let cookie = format!("access_token={token_str}; Path=/; Expires=Fri, 1 Jan 2010 00:00:00 UTC; HttpOnly; SameSite=None{SECURE}");
+    Ok(TariffImport { imported: tariffs.len() })
+}
+
+#[derive(Serialize)]
+struct RegionImport {
+    imported: usize,
+}
+
+/// csv with header of [`RegionInput::FIELDS`], nothing is imported if any row is invalid
+async fn import_regions(csv: &str, state: &PgPool) -> Result<RegionImport> {
+    let CsvRows { rows, errors } = csv_rows(csv, &RegionInput::FIELDS, RegionInput::from_record, ("kelurahan", RegionInput::key))?;
 
-        return Response::builder().header(SET_COOKIE, cookie).json(token);
+    if !errors.is_empty() {
+        return Err(Error::Validation(errors));
     }
 
-    match (&parts.method, path) {
-        (GET, "/logout") => Response::builder().header(SET_COOKIE, LOGOUT_COOKIE).empty(),
-        (GET, "/auth") => parts.get_session()?.into_response(),
-        _ => NOT_FOUND,
+    let regions = rows.into_iter().map(|(_,e)|e).collect::<Vec<_>>();
+    let mut tx = state.begin().await.fatal()?;
+    for chunk in regions.chunks(REGION_CHUNK) {
+        let column = |f: fn(&RegionInput) -> &String|chunk.iter().map(f).collect::<Vec<_>>();
+        sqlx::query(UPSERT_REGIONS)
+            .bind(column(|e|&e.provinsi)).bind(column(|e|&e.kabupaten)).bind(column(|e|&e.kecamatan))
+            .bind(column(|e|&e.kelurahan)).bind(column(|e|&e.kodepos))
+            .execute(&mut *tx).await.fatal()?;
     }
+    tx.commit().await.fatal()?;
+
+    Ok(RegionImport { imported: regions.len() })
 }
 
-async fn handle_orders(parts: &Parts, _: Body, state: &PgPool) -> Result {
-    let path = parts.normalize_prefix("/orders");
-    let (limit,page) = parts.parse_query();
-    match (&parts.method, path) {
-        (GET, BASE) => sqlx::query_as::<_, Orders>(SELECT_ORDERS)
-            .bind(limit as i32).bind(page as i32).fetch_all(state).await.fatal()?
-            .into_response(),
-        (GET, "/tracings") => sqlx::query_as::<_, Orders>(SELECT_ORDERS_TRACINGS)
-            .bind(limit as i32).bind(page as i32).fetch_all(state).await.fatal()?
-            .into_response(),
-        _ => NOT_FOUND,
+struct CsvRows<T> {
+    rows: Vec<(usize, T)>,
+    errors: Vec<FieldError>,
+}
+
+/// data rows of csv with header `fields`, numbered including header as seen in spreadsheet
+///
+/// invalid row is reported as `rows[n].field`, duplicate of `key` on its field
+fn csv_rows<T, K: Hash + Eq>(
+    csv: &str,
+    fields: &[&str],
+    parse: fn(&[String]) -> Result<T, Vec<FieldError>>,
+    (key_field, key): (&str, fn(&T) -> K),
+) -> Result<CsvRows<T>> {
+    let mut records = csv::parse(csv).into_iter();
+
+    let Some(header) = records.next() else {
+        return Err(Error::BadRequest("csv is empty".into()));
+    };
+    if header.iter().map(|e|e.trim()).ne(fields.iter().copied()) {
+        return Err(Error::BadRequest(format!("csv header must be `{}`", fields.join(","))));
+    }
+
+    let mut rows = vec![];
+    let mut errors = vec![];
+    let mut seen = HashMap::new();
+
+    for (row, record) in records.enumerate().map(|(i,e)|(i + 2, e)) {
+        match parse(&record) {
+            Ok(value) => match seen.insert(key(&value), row) {
+                Some(first) => errors.push(row_error(row, key_field, format!("duplicate of row {first}"))),
+                None => rows.push((row, value)),
+            },
+            Err(err) => errors.extend(err.into_iter().map(|e|row_error(row, &e.field, e.message))),
+        }
     }
+
+    Ok(CsvRows { rows, errors })
 }
 
-async fn handle_sales(parts: &Parts, body: Body, state: &PgPool) -> Result {
-    let path = parts.normalize_prefix("/sales");
+fn row_error(row: usize, field: &str, message: impl Into<String>) -> FieldError {
+    FieldError::new(format!("rows[{row}].{field}"), message)
+}
 
-    let (_session, sales) = parts.get_session_role(Sales)?.split::<SalesData>()?;
-    let (limit,page) = parts.parse_query();
+/// prefix match on kelurahan, kecamatan, kabupaten, or kodepos
+async fn search_regions(q: &str, page: Pagination, state: &PgPool) -> Result<(i64, Vec<Regions>)> {
+    let q = q.trim();
+    if q.chars().count() < REGION_QUERY_MIN {
+        return Err(Error::Validation(vec![FieldError::new("q", format!("q must be at least {REGION_QUERY_MIN} characters"))]));
+    }
 
-    match (&parts.method, path) {
-        (GET, BASE) => sqlx::query_as::<_, Orders>(SELECT_ORDER_STATUS_BY_WH_ID)
-            .bind(&sales.wh_id).bind(limit as i32).bind(page as i32).fetch_all(state).await.fatal()?
-            .into_response(),
-        (POST, BASE) => create_order(parts, &body.json().await?, &state).await?.into_response(),
-        _ => NOT_FOUND,
+    let escaped = q.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    let total = sqlx::query_scalar::<_, i64>(COUNT_REGIONS_SEARCH)
+        .bind(&escaped).fetch_one(state).await.fatal()?;
+    let data = sqlx::query_as::<_, Regions>(SEARCH_REGIONS)
+        .bind(&escaped).bind(page.limit()).bind(page.offset())
+        .fetch_all(state).await.fatal()?;
+    Ok((total, data))
+}
+
+/// case insensitive [`WhType`] variant
+fn parse_wh_type(value: &str) -> Result<WhType> {
+    WhType::VARIANTS.iter()
+        .find(|e|e.eq_ignore_ascii_case(value))
+        .and_then(|e|WhType::from_str(e).ok())
+        .ok_or_else(||Error::BadRequest(format!("wh_type must be one of {}", WhType::VARIANTS.join(", "))))
+}
+
+/// unique and foreign key violation is `409`, check violation is `400`
+fn conflict(err: sqlx::Error) -> Error {
+    match err.as_database_error() {
+        Some(e) if e.is_unique_violation() || e.is_foreign_key_violation() => Error::Http(StatusCode::CONFLICT),
+        Some(e) if e.is_check_violation() => {
+            let field = match e.constraint() {
+                Some("users_metadata_schema") => "metadata",
+                Some(constraint) => constraint,
+                None => "body",
+            };
+            Error::Validation(vec![FieldError::new(field, "value does not satisfy the schema")])
+        },
+        _ => Error::InternalError(err.to_string()),
     }
 }
 
@@ -148,33 +1050,334 @@ struct CreateOrder {
     sender: UserAnon,
     receiver: UserAnon,
     destination: Destination,
-    packages: Vec<Package>
+    packages: Vec<Package>,
+    #[serde(default)]
+    service: Service,
 }
 
-async fn create_order(_: &Parts, data: &CreateOrder, state: &PgPool) -> Result<OrderId> {
+#[derive(Deserialize)]
+struct QuoteRequest {
+    destination: Destination,
+    packages: Vec<Package>,
+    #[serde(default)]
+    service: Service,
+}
+
+async fn quote<'e, E>(wh_id: &WhId, destination: &Destination, service: Service, packages: &[Package], state: E) -> Result<Quote>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let Some(tariff) = sqlx::query_as::<_, Tariffs>(FIND_TARIFFS_EFFECTIVE)
+        .bind(wh_id).bind(&destination.provinsi).bind(&destination.kabupaten).bind(service.as_str())
+        .fetch_optional(state).await.fatal()? else
+    {
+        return Err(Error::Logic(LogicError::TariffNotFound(format!("{}, {} ({})",
+            destination.kabupaten, destination.provinsi, service.as_str()))));
+    };
+
+    tariff.quote(packages).map_err(Error::Validation)
+}
+
+#[derive(Serialize)]
+struct OrderCreated {
+    order: Orders,
+    quote: Quote,
+    tracing_id: TracingId,
+    sales_sid: UserSid,
+    wh_sid: WhSid,
+}
+
+async fn create_order(session: &Token, sales: &SalesData, data: &CreateOrder, state: &PgPool) -> Result<OrderCreated> {
     let mut tx = state.begin().await.fatal()?;
 
-    let sender_sid = snapshot_anon(&data.sender, &mut *tx).await?;
-    let receiver_sid = snapshot_anon(&data.receiver, &mut *tx).await?;
+    let sender_sid = snapshot_anon(&data.sender, &mut tx).await?;
+    let receiver_sid = snapshot_anon(&data.receiver, &mut tx).await?;
+    let sales_sid = snapshot_user(&session.user_id, &mut tx).await?;
+    let wh_sid = snapshot_wh(&sales.wh_id, &mut tx).await?;
+
+    let mut destination = data.destination.clone();
+    let region = sqlx::query_as::<_, Regions>(FIND_REGIONS_BY_HIERARCHY)
+        .bind(&destination.provinsi).bind(&destination.kabupaten)
+        .bind(&destination.kecamatan).bind(&destination.kelurahan)
+        .fetch_optional(&mut *tx).await.fatal()?;
 
-    let res = sqlx::query_scalar(INSERT_ORDERS)
-        .bind(&sender_sid).bind(&receiver_sid)
-        .bind(&data.destination.json_str()?).bind(&data.packages.json_str()?)
+    // destination is accepted as is until reference dataset is loaded
+    let loaded = region.is_some() || sqlx::query_scalar::<_, bool>(EXISTS_REGIONS)
         .fetch_one(&mut *tx).await.fatal()?;
+    if loaded {
+        destination.conform(region.as_ref()).map_err(Error::Validation)?;
+    }
+
+    let quote = quote(&sales.wh_id, &destination, data.service, &data.packages, &mut *tx).await?;
+
+    let destination = destination.json_str()?;
+    let packages = data.packages.json_str()?;
+
+    // retry on resi collision
+    let mut order = None;
+    for _ in 0..RESI_RETRY {
+        order = sqlx::query_as::<_, Orders>(CREATE_ORDERS)
+            .bind(sender_sid).bind(receiver_sid).bind(&destination).bind(&packages)
+            .bind(Resi::new(&sales.wh_id, OsRng.gen_range(0..Resi::RANDOM_RANGE)).as_str())
+            .bind(quote.service.as_str()).bind(quote.price).bind(quote.tariff_id)
+            .fetch_optional(&mut *tx).await.fatal()?;
+        if order.is_some() { break; }
+    }
+    let Some(order) = order else {
+        return Err(Error::InternalError("resi generation exhausted".into()));
+    };
+
+    let tracing_id = sqlx::query_scalar::<_, TracingId>(CREATE_TRACING)
+        .bind(order.order_id).bind(sales_sid).bind(wh_sid).bind(Status::Warehouse.as_str())
+        .fetch_one(&mut *tx).await.fatal()?;
+
+    sqlx::query(INSERT_ORDER_STATUS)
+        .bind(order.order_id).bind(tracing_id).bind(sales.wh_id)
+        .execute(&mut *tx).await.fatal()?;
 
     tx.commit().await.fatal()?;
 
-    Ok(res)
+    Ok(OrderCreated { order, quote, tracing_id, sales_sid, wh_sid })
+}
+
+#[derive(Deserialize)]
+struct GatewayOut {
+    orders: Vec<OrderId>,
+    destination: WhId,
+    driver: UserAnon,
+}
+
+async fn gateway_out(session: &Token, sales: &SalesData, data: &GatewayOut, state: &PgPool) -> Result<Manifests> {
+    let order_ids = unique_orders(&data.orders)?;
+
+    if data.destination.0 == sales.wh_id.0 {
+        return Err(Error::Logic(LogicError::SameWarehouse(data.destination.0)));
+    }
+
+    let mut tx = state.begin().await.fatal()?;
+
+    let available = sqlx::query_scalar::<_, i32>(SELECT_ORDER_STATUS_IN_WH)
+        .bind(&order_ids).bind(sales.wh_id).bind(Status::Warehouse.as_str())
+        .fetch_all(&mut *tx).await.fatal()?;
+
+    if let Some(id) = order_ids.iter().find(|id|!available.contains(id)) {
+        return Err(Error::Logic(LogicError::OrderNotAvailable(*id)));
+    }
+
+    if let Some(id) = &data.driver.user_id {
+        ensure_role(id, Driver, &mut tx).await?;
+    }
+
+    let sales_sid = snapshot_user(&session.user_id, &mut tx).await?;
+    let driver_sid = snapshot_anon(&data.driver, &mut tx).await?;
+    let wh_from_sid = snapshot_wh(&sales.wh_id, &mut tx).await?;
+    let wh_to_sid = snapshot_wh(&data.destination, &mut tx).await?;
+
+    let manifest = sqlx::query_as::<_, Manifests>(INSERT_MANIFESTS)
+        .bind(sales_sid).bind(driver_sid).bind(wh_from_sid).bind(wh_to_sid)
+        .fetch_one(&mut *tx).await.fatal()?;
+
+    for order_id in order_ids {
+        sqlx::query(INSERT_MANIFEST_ORDERS)
+            .bind(manifest.manifest_id).bind(order_id)
+            .execute(&mut *tx).await.fatal()?;
+
+        let tracing_id = sqlx::query_scalar::<_, TracingId>(CREATE_TRACING)
+            .bind(order_id).bind(driver_sid).bind(wh_to_sid).bind(Status::Driver.as_str())
+            .fetch_one(&mut *tx).await.fatal()?;
+
+        recreate_order_status(order_id, &tracing_id, &sales.wh_id, &mut tx).await?;
+    }
+
+    tx.commit().await.fatal()?;
+
+    Ok(manifest)
+}
+
+async fn gateway_in(session: &Token, sales: &SalesData, id: &ManifestId, state: &PgPool) -> Result<Manifests> {
+    let mut tx = state.begin().await.fatal()?;
+
+    let Some(manifest) = sqlx::query_as::<_, Manifests>(FIND_MANIFESTS_FOR_UPDATE)
+        .bind(id).fetch_optional(&mut *tx).await.fatal()? else
+    {
+        return Err(Error::Logic(LogicError::ManifestIdNotFound(id.0)));
+    };
+
+    if manifest.completed_at.is_some() {
+        return Err(Error::Logic(LogicError::ManifestCompleted(id.0)));
+    }
+
+    let wh_to = sqlx::query_scalar::<_, i32>(FIND_WH_SN_WH_ID)
+        .bind(manifest.wh_to_sid).fetch_one(&mut *tx).await.fatal()?;
+
+    if wh_to != sales.wh_id.0 {
+        return Err(Error::Logic(LogicError::ManifestWrongWarehouse(id.0)));
+    }
+
+    let order_ids = sqlx::query_scalar::<_, i32>(SELECT_MANIFEST_ORDERS_BY_STATUS)
+        .bind(id).bind(Status::Driver.as_str())
+        .fetch_all(&mut *tx).await.fatal()?;
+
+    let sales_sid = snapshot_user(&session.user_id, &mut tx).await?;
+    let wh_sid = snapshot_wh(&sales.wh_id, &mut tx).await?;
+
+    for order_id in order_ids {
+        let tracing_id = sqlx::query_scalar::<_, TracingId>(CREATE_TRACING)
+            .bind(order_id).bind(sales_sid).bind(wh_sid).bind(Status::Warehouse.as_str())
+            .fetch_one(&mut *tx).await.fatal()?;
+
+        recreate_order_status(order_id, &tracing_id, &sales.wh_id, &mut tx).await?;
+    }
+
+    let manifest = sqlx::query_as::<_, Manifests>(COMPLETE_MANIFESTS)
+        .bind(id).fetch_one(&mut *tx).await.fatal()?;
+
+    tx.commit().await.fatal()?;
+
+    Ok(manifest)
+}
+
+#[derive(Deserialize)]
+struct Completion {
+    orders: Vec<OrderId>,
+}
+
+#[derive(Serialize)]
+struct Completed {
+    order_id: i32,
+    tracing_id: TracingId,
+}
+
+async fn complete_sales(session: &Token, sales: &SalesData, data: &Completion, state: &PgPool) -> Result<Vec<Completed>> {
+    let order_ids = unique_orders(&data.orders)?;
+
+    let mut tx = state.begin().await.fatal()?;
+
+    let available = sqlx::query_scalar::<_, i32>(SELECT_ORDER_STATUS_IN_WH)
+        .bind(&order_ids).bind(sales.wh_id).bind(Status::Warehouse.as_str())
+        .fetch_all(&mut *tx).await.fatal()?;
+
+    if let Some(id) = order_ids.iter().find(|id|!available.contains(id)) {
+        return Err(Error::Logic(LogicError::OrderNotAvailable(*id)));
+    }
+
+    let sales_sid = snapshot_user(&session.user_id, &mut tx).await?;
+    let wh_sid = snapshot_wh(&sales.wh_id, &mut tx).await?;
+    let orders = order_ids.into_iter().map(|id|(id, wh_sid)).collect();
+
+    let completed = complete_orders(&sales_sid, orders, &mut tx).await?;
+
+    tx.commit().await.fatal()?;
+
+    Ok(completed)
+}
+
+async fn complete_driver(session: &Token, data: &Completion, state: &PgPool) -> Result<Vec<Completed>> {
+    let order_ids = unique_orders(&data.orders)?;
+
+    let mut tx = state.begin().await.fatal()?;
+
+    let carried = sqlx::query_as::<_, (i32, WhSid)>(SELECT_ORDER_STATUS_BY_DRIVER)
+        .bind(&order_ids).bind(Status::Driver.as_str()).bind(session.user_id)
+        .fetch_all(&mut *tx).await.fatal()?;
+
+    if let Some(id) = order_ids.iter().find(|id|!carried.iter().any(|(e,_)|e == *id)) {
+        return Err(Error::Logic(LogicError::OrderNotCarried(*id)));
+    }
+
+    let driver_sid = snapshot_user(&session.user_id, &mut tx).await?;
+
+    let completed = complete_orders(&driver_sid, carried, &mut tx).await?;
+
+    // locked before checking, so concurrent completion of the last orders
+    // of the same manifest see each other
+    let manifest_ids = sqlx::query_scalar::<_, i32>(LOCK_OPEN_MANIFESTS_BY_ORDERS)
+        .bind(&order_ids).fetch_all(&mut *tx).await.fatal()?;
+    sqlx::query(COMPLETE_MANIFESTS_DELIVERED)
+        .bind(&manifest_ids).bind(Status::Driver.as_str())
+        .execute(&mut *tx).await.fatal()?;
+
+    tx.commit().await.fatal()?;
+
+    Ok(completed)
+}
+
+/// `Completed` tracing for every orders, and remove its `order_status`
+async fn complete_orders(subject_sid: &UserSid, orders: Vec<(i32, WhSid)>, state: &mut PgConnection) -> Result<Vec<Completed>> {
+    let mut completed = Vec::with_capacity(orders.len());
+
+    for (order_id, wh_sid) in orders {
+        let tracing_id = sqlx::query_scalar::<_, TracingId>(CREATE_TRACING)
+            .bind(order_id).bind(subject_sid).bind(wh_sid).bind(Status::Completed.as_str())
+            .fetch_one(&mut *state).await.fatal()?;
+
+        sqlx::query(DELETE_ORDER_STATUS).bind(order_id).execute(&mut *state).await.fatal()?;
+
+        completed.push(Completed { order_id, tracing_id });
+    }
+
+    Ok(completed)
+}
+
+fn unique_orders(orders: &[OrderId]) -> Result<Vec<i32>> {
+    let mut order_ids = orders.iter().map(|e|e.0).collect::<Vec<_>>();
+    order_ids.sort_unstable();
+    order_ids.dedup();
+
+    match order_ids.is_empty() {
+        true => Err(Error::BadRequest("orders cannot be empty".into())),
+        false => Ok(order_ids),
+    }
+}
+
+async fn recreate_order_status(order_id: i32, tracing_id: &TracingId, wh_id: &WhId, state: &mut PgConnection) -> Result<()> {
+    sqlx::query(DELETE_ORDER_STATUS).bind(order_id).execute(&mut *state).await.fatal()?;
+    sqlx::query(INSERT_ORDER_STATUS)
+        .bind(order_id).bind(tracing_id).bind(wh_id)
+        .execute(state).await.fatal()?;
+    Ok(())
+}
+
+async fn ensure_role(id: &UserId, role: Role, state: &mut PgConnection) -> Result<()> {
+    match sqlx::query_scalar::<_, Role>(FIND_USERS_ROLE).bind(id).fetch_optional(state).await.fatal()? {
+        Some(found) if found == role => Ok(()),
+        Some(_) => Err(Error::Logic(LogicError::RoleMismatch(id.0, role))),
+        None => Err(Error::Logic(LogicError::UserIdNotFound(id.0))),
+    }
+}
+
+/// latest snapshot of registered user, snapshot is created if none exists
+async fn snapshot_user(id: &UserId, state: &mut PgConnection) -> Result<UserSid> {
+    if let Some(sid) = sqlx::query_scalar(FIND_LATEST_USERS_SN)
+        .bind(id).fetch_optional(&mut *state).await.fatal()?
+    {
+        return Ok(sid);
+    }
+    match sqlx::query_scalar(SNAPSHOT_USERS).bind(id).fetch_optional(state).await.fatal()? {
+        Some(sid) => Ok(sid),
+        None => Err(Error::Logic(LogicError::UserIdNotFound(id.0))),
+    }
+}
+
+/// latest snapshot of active warehouse, snapshot is created if none exists
+async fn snapshot_wh(id: &WhId, state: &mut PgConnection) -> Result<WhSid> {
+    ensure_wh(id, &mut *state).await?;
+
+    if let Some(sid) = sqlx::query_scalar(FIND_LATEST_WH_SN)
+        .bind(id).fetch_optional(&mut *state).await.fatal()?
+    {
+        return Ok(sid);
+    }
+    match sqlx::query_scalar(SNAPSHOT_WH).bind(id).fetch_optional(state).await.fatal()? {
+        Some(sid) => Ok(sid),
+        None => Err(Error::Logic(LogicError::WhIdNotFound(id.0))),
+    }
 }
 
 async fn snapshot_anon(anon: &UserAnon, state: &mut PgConnection) -> Result<UserSid> {
     match &anon.user_id {
-        Some(id) => match sqlx::query_scalar(ID_USERS_SN).fetch_optional(state).await.fatal()? {
-            Some(row) => Ok(row),
-            None => Err(Error::Logic(http_core::LogicError::UserIdNotFound(id.0))),
-        },
+        Some(id) => snapshot_user(id, state).await,
         None => sqlx::query_scalar(CREATE_USERS_SN)
             .bind(anon.json_str()?).fetch_one(state).await.fatal(),
     }
 }
-
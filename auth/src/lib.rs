@@ -1,16 +1,29 @@
-use std::fmt::{Debug, Display};
+use std::{fmt::{Debug, Display}, future::Future};
 
+use chrono::Utc;
 use serde::de::DeserializeOwned;
 use serde_json::Value;
+use sqlx::PgPool;
 use tracing::debug;
-use types::{Date, Deserialize, Serialize, UserId, Users, WhId, WhType};
-use argon2::{password_hash::{Error::Password, Result as ArgonResult}, Argon2, PasswordHash, PasswordVerifier as _};
+use types::{Date, Deserialize, DriverMetadata, Serialize, UserId, Users, WhId, WhType};
+use argon2::{password_hash::{Error::Password, Result as ArgonResult, SaltString}, Argon2, PasswordHash, PasswordHasher as _, PasswordVerifier as _};
 
+pub use chrono::TimeDelta;
 pub use types::Role;
+
+/// default access token lifetime
+pub const DEFAULT_TTL: TimeDelta = TimeDelta::minutes(15);
+/// default refresh token lifetime
+pub const DEFAULT_REFRESH_TTL: TimeDelta = TimeDelta::days(30);
+/// tolerated clock skew when validating token time claims
+pub const LEEWAY: TimeDelta = TimeDelta::seconds(60);
 pub const DUMMY_PASSWD: &str = "$argon2id$v=19$m=19456,t=2,p=1$jZlzXaKWE9bOcXz99qDobg$L8MH9ZkgV/gdIhWQ72tNhDhmX4gPkdlzIUNfIF2oO4k";
 
+/// jwt claims, `sub`, `iat`, `nbf` and `exp` are registered claims
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Token {
+    #[serde(default)]
+    pub sub: String,
     pub user_id: UserId,
     pub name: String,
     pub phone: String,
@@ -20,9 +33,50 @@ pub struct Token {
     pub created_at: Date,
     pub updated_at: Date,
     pub verified_at: Option<Date>,
-    pub signed_at: Date,
+    #[serde(rename = "iat", alias = "issued_at", with = "claim_time")]
+    pub issued_at: Date,
+    #[serde(rename = "nbf", alias = "not_before", with = "claim_time")]
+    pub not_before: Date,
+    #[serde(rename = "exp", alias = "expires_at", with = "claim_time")]
+    pub expires_at: Date,
+}
+
+/// numeric date, rfc3339 string from previous token format is still accepted
+mod claim_time {
+    use chrono::DateTime;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use types::Date;
+
+    pub fn serialize<S: Serializer>(date: &Date, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_i64(date.timestamp())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Date, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Time { Numeric(i64), Rfc3339(Date) }
+
+        match Time::deserialize(d)? {
+            Time::Numeric(secs) => DateTime::from_timestamp(secs, 0).ok_or_else(||D::Error::custom("timestamp out of range")),
+            Time::Rfc3339(date) => Ok(date),
+        }
+    }
+}
+
+/// typed payload stored in [`Token::role_data`]
+pub trait RoleData: Serialize + DeserializeOwned + Sized {
+    const ROLE: Role;
+
+    /// `None` if user does not have required data for its role
+    fn load(user: &Users, db: &PgPool) -> impl Future<Output = sqlx::Result<Option<Self>>> + Send;
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AdminData { }
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CustomerData { }
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SalesData {
     pub wh_id: WhId,
@@ -30,9 +84,62 @@ pub struct SalesData {
     pub wh_type: WhType,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DriverData {
+    pub metadata: DriverMetadata,
+}
+
+impl RoleData for AdminData {
+    const ROLE: Role = Role::Admin;
+    async fn load(_: &Users, _: &PgPool) -> sqlx::Result<Option<Self>> { Ok(Some(Self { })) }
+}
+
+impl RoleData for CustomerData {
+    const ROLE: Role = Role::Customer;
+    async fn load(_: &Users, _: &PgPool) -> sqlx::Result<Option<Self>> { Ok(Some(Self { })) }
+}
+
+impl RoleData for SalesData {
+    const ROLE: Role = Role::Sales;
+    async fn load(user: &Users, db: &PgPool) -> sqlx::Result<Option<Self>> {
+        let wh = sqlx::query_as::<_, (WhId, String, WhType)>(sql::FIND_EMPLOYEES_WH)
+            .bind(user.user_id).fetch_optional(db).await?;
+        Ok(wh.map(|(wh_id, wh_name, wh_type)|Self { wh_id, wh_name, wh_type }))
+    }
+}
+
+impl RoleData for DriverData {
+    const ROLE: Role = Role::Driver;
+    async fn load(user: &Users, _: &PgPool) -> sqlx::Result<Option<Self>> {
+        match serde_json::from_value(user.metadata.clone()) {
+            Ok(metadata) => Ok(Some(Self { metadata })),
+            Err(error) => {
+                debug!(target: "role data", %error, "invalid driver metadata");
+                Ok(None)
+            },
+        }
+    }
+}
+
+/// load [`RoleData`] corresponding to user role
+pub async fn load_role_data(user: &Users, db: &PgPool) -> sqlx::Result<Option<Value>> {
+    async fn load<T: RoleData>(user: &Users, db: &PgPool) -> sqlx::Result<Option<Value>> {
+        Ok(T::load(user, db).await?.map(|data|serde_json::to_value(data).expect("role data is serializable")))
+    }
+
+    match user.role {
+        Role::Admin => load::<AdminData>(user, db).await,
+        Role::Customer => load::<CustomerData>(user, db).await,
+        Role::Sales => load::<SalesData>(user, db).await,
+        Role::Driver => load::<DriverData>(user, db).await,
+    }
+}
+
 impl Token {
-    pub fn new(user: Users, role_data: Value) -> Self {
+    pub fn new(user: Users, role_data: Value, ttl: TimeDelta) -> Self {
+        let now = Utc::now();
         Self {
+            sub: user.user_id.0.to_string(),
             user_id: user.user_id,
             name: user.name,
             phone: user.phone,
@@ -42,26 +149,58 @@ impl Token {
             created_at: user.created_at,
             updated_at: user.updated_at,
             verified_at: user.verified_at,
-            signed_at: Default::default()
+            issued_at: now,
+            not_before: now,
+            expires_at: now + ttl,
         }
     }
 
-    pub fn from_token_str(secret: &str, token_str: &str) -> Result<Self> {
-        let Some(body) = sign::verify(secret, token_str) else {
+    pub fn from_token_str(keyring: &sign::Keyring, token_str: &str) -> Result<Self> {
+        let Some(body) = keyring.verify(token_str) else {
             debug!(target: "login failed","invalid hmac");
             return Err(Error::Unauthorized);
         };
 
-        match serde_json::from_str(&body) {
-            Ok(token) => Ok(token),
+        let token = match serde_json::from_str::<Self>(&body) {
+            Ok(token) => token,
             Err(error) => {
                 tracing::error!(target: "assertion failed", %error, "token deserialization");
-                Err(Error::InvalidToken)
+                return Err(Error::InvalidToken);
             },
+        };
+
+        if !token.sub.is_empty() && token.sub != token.user_id.0.to_string() {
+            tracing::error!(target: "assertion failed", "token subject mismatch");
+            return Err(Error::InvalidToken);
+        }
+
+        token.validate_time(Utc::now())?;
+        Ok(token)
+    }
+
+    /// check time claims, tolerating [`LEEWAY`] of clock skew
+    pub fn validate_time(&self, now: Date) -> Result<()> {
+        if now + LEEWAY < self.not_before || now + LEEWAY < self.issued_at {
+            debug!(target: "login failed","token used before valid");
+            return Err(Error::InvalidToken);
+        }
+        if now - LEEWAY >= self.expires_at {
+            debug!(target: "login failed","token expired");
+            return Err(Error::TokenExpired);
         }
+        Ok(())
     }
 
-    pub fn split<T>(mut self) -> Result<(Self, T)> where T: DeserializeOwned {
+    /// token lifetime in seconds, for cookie `Max-Age`
+    pub fn max_age(&self) -> i64 {
+        (self.expires_at - self.issued_at).num_seconds()
+    }
+
+    pub fn split<T>(mut self) -> Result<(Self, T)> where T: RoleData {
+        if self.role != T::ROLE {
+            return Err(Error::Forbidden);
+        }
+
         match serde_json::from_value(self.role_data.take()) {
             Ok(role_data) => Ok((self,role_data)),
             Err(error) => {
@@ -81,7 +220,7 @@ impl Token {
 pub fn verify_passwd(password: &str, hashed: &str) -> ArgonResult<Option<()>> {
     if cfg!(test) { return Ok((password == hashed).then_some(())) }
 
-    let parsed_hash = PasswordHash::new(&hashed)?;
+    let parsed_hash = PasswordHash::new(hashed)?;
     let passwd_ok = Argon2::default().verify_password(password.as_bytes(), &parsed_hash);
 
     match passwd_ok {
@@ -94,6 +233,12 @@ pub fn verify_passwd(password: &str, hashed: &str) -> ArgonResult<Option<()>> {
     }
 }
 
+/// This is cpu bound process, call it with [`tokio::task::spawn_blocking`]
+pub fn hash_passwd(password: &str) -> ArgonResult<String> {
+    let salt = SaltString::generate(&mut rand::rngs::OsRng);
+    Ok(Argon2::default().hash_password(password.as_bytes(), &salt)?.to_string())
+}
+
 /// This is cpu bound process, call it with [`tokio::task::spawn_blocking`]
 ///
 /// this is used to prevent timing attack, when the user not found in database
@@ -115,7 +260,11 @@ pub enum Error {
     Unauthorized,
     InvalidCredential,
     Forbidden,
-    InvalidToken
+    InvalidToken,
+    TokenExpired,
+    TokenReused,
+    NoRoleData,
+    Deactivated,
 }
 
 impl Error {
@@ -126,6 +275,10 @@ impl Error {
             Error::InvalidCredential => "Invalid Credential",
             Error::Forbidden => "Forbidden",
             Error::InvalidToken => "Invalid Token",
+            Error::TokenExpired => "Token Expired",
+            Error::TokenReused => "Token Reused",
+            Error::NoRoleData => "No Role Data",
+            Error::Deactivated => "Account Deactivated",
         }
     }
     pub const fn message(&self) -> &'static str {
@@ -134,6 +287,10 @@ impl Error {
             Error::InvalidCredential => "Invalid phone or password",
             Error::Forbidden => "You are not allowed to access this resource",
             Error::InvalidToken => "Token invalid, please issue a new token",
+            Error::TokenExpired => "Token expired, please login again",
+            Error::TokenReused => "Session revoked due to token reuse, please login again",
+            Error::NoRoleData => "Your account is not fully set up for its role, please contact admin",
+            Error::Deactivated => "Your account is deactivated, please contact admin",
         }
     }
 }
@@ -150,13 +307,215 @@ impl Debug for Error {
     }
 }
 
+pub mod refresh {
+    use rand::{rngs::OsRng, RngCore as _};
+    use sha2::{Digest as _, Sha256};
+    use crate::sign::to_base;
+
+    /// opaque refresh token, only its [`hash`] is stored
+    pub fn generate() -> String {
+        let mut bytes = [0u8;32];
+        OsRng.fill_bytes(&mut bytes);
+        to_base(bytes)
+    }
+
+    pub fn hash(token: &str) -> String {
+        to_base(Sha256::digest(token.as_bytes()))
+    }
+}
+
 pub mod sign {
+    use std::{env::var, fs, sync::LazyLock};
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Serialize};
     use sha2::Sha256;
     use hmac::{Hmac, Mac};
     use base64::prelude::*;
+    use types::Date;
 
     type Sign = Hmac<Sha256>;
 
+    static KEYRING: LazyLock<Keyring> = LazyLock::new(||Keyring::from_env().expect("checked keyring"));
+
+    /// keyring loaded from env, check with [`Keyring::from_env`] at startup
+    pub fn keyring() -> &'static Keyring { &KEYRING }
+
+    pub struct Key {
+        pub id: String,
+        secret: String,
+        /// key is no longer accepted for verification after this time
+        pub retire_at: Option<Date>,
+    }
+
+    pub const ALG: &str = "HS256";
+    pub const TYP: &str = "JWT";
+
+    #[derive(Serialize, Deserialize)]
+    struct Header {
+        alg: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        typ: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        kid: Option<String>,
+    }
+
+    /// one signing key and multiple verification keys,
+    /// token is jws compact serialization with key id in `kid` header
+    pub struct Keyring {
+        signing: usize,
+        keys: Vec<Key>,
+        /// previous `kid.payload.signature` format is rejected after this time
+        legacy_until: Option<Date>,
+    }
+
+    impl Keyring {
+        /// keys are read from file at `JWT_KEYS_FILE`, or `JWT_KEYS`,
+        /// or fallback to single `JWT_SECRET` with id `default`
+        ///
+        /// keys are separated by newline or `;`, each key is `<id> <secret> [retire_at]`,
+        /// with `retire_at` in rfc3339
+        ///
+        /// signing key is `JWT_SIGNING_KID`, or the first key, and cannot have `retire_at`
+        ///
+        /// previous token format is accepted until `JWT_LEGACY_UNTIL` in rfc3339
+        pub fn from_env() -> Result<Self, String> {
+            let keys = if let Ok(path) = var("JWT_KEYS_FILE") {
+                fs::read_to_string(&path).map_err(|e|format!("JWT_KEYS_FILE `{path}`: {e}"))?
+            } else if let Ok(keys) = var("JWT_KEYS") {
+                keys
+            } else if let Ok(secret) = var("JWT_SECRET") {
+                format!("default {secret}")
+            } else {
+                return Err("JWT_KEYS_FILE, JWT_KEYS or JWT_SECRET: env required".into());
+            };
+
+            let keyring = Self::parse(&keys, var("JWT_SIGNING_KID").ok().as_deref())?;
+
+            match var("JWT_LEGACY_UNTIL") {
+                Ok(at) => Ok(keyring.legacy_until(DateTime::parse_from_rfc3339(&at)
+                    .map_err(|e|format!("JWT_LEGACY_UNTIL: {e}"))?.to_utc())),
+                Err(_) => Ok(keyring),
+            }
+        }
+
+        pub fn legacy_until(mut self, at: Date) -> Self {
+            self.legacy_until = Some(at);
+            self
+        }
+
+        pub fn parse(keys: &str, signing_kid: Option<&str>) -> Result<Self, String> {
+            let mut parsed = vec![];
+
+            for line in keys.split(['\n',';']).map(str::trim).filter(|e|!e.is_empty() && !e.starts_with('#')) {
+                let mut fields = line.split_whitespace();
+                let (Some(id), Some(secret)) = (fields.next(), fields.next()) else {
+                    return Err(format!("invalid key entry `{line}`"));
+                };
+                if id.contains('.') {
+                    return Err(format!("key id `{id}` cannot contain `.`"));
+                }
+                let retire_at = match fields.next() {
+                    Some(at) => Some(DateTime::parse_from_rfc3339(at)
+                        .map_err(|e|format!("key `{id}` retire_at: {e}"))?.to_utc()),
+                    None => None,
+                };
+                parsed.push(Key { id: id.into(), secret: secret.into(), retire_at });
+            }
+
+            let signing = match signing_kid {
+                Some(kid) => parsed.iter().position(|e|e.id == kid)
+                    .ok_or_else(||format!("signing key `{kid}` not found"))?,
+                None if parsed.is_empty() => return Err("no key found".into()),
+                None => 0,
+            };
+
+            // checked only once here, a retiring signing key would keep
+            // issuing tokens that are rejected once it retire
+            if parsed[signing].retire_at.is_some() {
+                return Err(format!("signing key `{}` cannot have retire_at", parsed[signing].id));
+            }
+
+            Ok(Self { signing, keys: parsed, legacy_until: None })
+        }
+
+        pub fn signing_key(&self) -> &Key { &self.keys[self.signing] }
+
+        pub fn sign(&self, msg: &str) -> String {
+            let key = self.signing_key();
+            let header = Header { alg: ALG.into(), typ: Some(TYP.into()), kid: Some(key.id.clone()) };
+            let input = to_base(serde_json::to_vec(&header).expect("header is serializable")) + "." + &to_base(msg);
+            let signature = to_base(mac(&key.secret, &input).finalize().into_bytes());
+            input + "." + &signature
+        }
+
+        /// retired key is rejected
+        pub fn verify(&self, value: &str) -> Option<String> {
+            let (header, rest) = value.split_once(".")?;
+
+            let Some(header) = from_base(header).and_then(|e|serde_json::from_slice::<Header>(&e).ok()) else {
+                return self.verify_legacy(value);
+            };
+
+            if header.alg != ALG || header.typ.as_deref().is_some_and(|typ|!typ.eq_ignore_ascii_case(TYP)) {
+                tracing::debug!(target: "login failed", alg = header.alg, "unsupported header");
+                return None;
+            }
+
+            let key = match header.kid {
+                Some(kid) => self.find_key(&kid)?,
+                None => self.signing_key(),
+            };
+
+            let (payload, signature) = rest.split_once(".")?;
+            let input = &value[..value.len() - signature.len() - 1];
+            mac(&key.secret, input).verify_slice(&from_base(signature)?).ok()?;
+            String::from_utf8(from_base(payload)?).ok()
+        }
+
+        /// compact `kid.payload.signature` for value other than token, `purpose` is
+        /// part of the signed input so value sealed for one purpose is rejected by another
+        pub fn seal(&self, purpose: &str, msg: &str) -> String {
+            let key = self.signing_key();
+            let input = key.id.clone() + "." + &to_base(msg);
+            let signature = to_base(mac(&key.secret, &format!("{purpose}.{input}")).finalize().into_bytes());
+            input + "." + &signature
+        }
+
+        /// retired key is rejected
+        pub fn unseal(&self, purpose: &str, value: &str) -> Option<String> {
+            let (input, signature) = value.rsplit_once(".")?;
+            let (kid, payload) = input.split_once(".")?;
+            let key = self.find_key(kid)?;
+            mac(&key.secret, &format!("{purpose}.{input}")).verify_slice(&from_base(signature)?).ok()?;
+            String::from_utf8(from_base(payload)?).ok()
+        }
+
+        fn verify_legacy(&self, value: &str) -> Option<String> {
+            if self.legacy_until.is_some_and(|at|at <= Utc::now()) {
+                tracing::debug!(target: "login failed", "legacy token format");
+                return None;
+            }
+            let (kid, value) = value.split_once(".")?;
+            verify(&self.find_key(kid)?.secret, value)
+        }
+
+        fn find_key(&self, kid: &str) -> Option<&Key> {
+            let key = self.keys.iter().find(|e|e.id == kid)?;
+            if key.retire_at.is_some_and(|at|at <= Utc::now()) {
+                tracing::debug!(target: "login failed", kid, "retired key");
+                return None;
+            }
+            Some(key)
+        }
+    }
+
+    fn mac(key: &str, msg: &str) -> Sign {
+        let mut mac = Sign::new_from_slice(key.as_bytes())
+            .expect("HMAC can take key of any size");
+        mac.update(msg.as_bytes());
+        mac
+    }
+
     pub fn sign(key: &str, msg: &str) -> String {
         let msg = to_base(msg);
         let mut mac = Sign::new_from_slice(key.as_bytes())
@@ -181,4 +540,154 @@ pub mod sign {
     pub fn from_base(value: &str) -> Option<Vec<u8>> {
         BASE64_URL_SAFE_NO_PAD.decode(value).ok()
     }
+
+    #[cfg(test)]
+    mod tests {
+        use chrono::TimeDelta;
+        use super::*;
+
+        const MSG: &str = r#"{"user_id":1}"#;
+
+        fn keyring(keys: &str) -> Keyring {
+            Keyring::parse(keys, None).unwrap()
+        }
+
+        fn hour() -> TimeDelta { TimeDelta::hours(1) }
+
+        #[test]
+        fn sign_verify_round_trip() {
+            let ring = keyring("k1 secret");
+            let token = ring.sign(MSG);
+            assert_eq!(ring.verify(&token).as_deref(), Some(MSG));
+            assert_eq!(keyring("k1 other").verify(&token), None);
+        }
+
+        #[test]
+        fn tampered_payload_rejected() {
+            let ring = keyring("k1 secret");
+            let token = ring.sign(MSG);
+            let (header, rest) = token.split_once('.').unwrap();
+            let (_, signature) = rest.split_once('.').unwrap();
+            let forged = format!("{header}.{}.{signature}", to_base(r#"{"user_id":2}"#));
+            assert_eq!(ring.verify(&forged), None);
+        }
+
+        #[test]
+        fn alg_other_than_hs256_rejected() {
+            let ring = keyring("k1 secret");
+            for alg in ["none", "HS512", "RS256"] {
+                let header = Header { alg: alg.into(), typ: Some(TYP.into()), kid: Some("k1".into()) };
+                let input = to_base(serde_json::to_vec(&header).unwrap()) + "." + &to_base(MSG);
+                let signature = to_base(mac("secret", &input).finalize().into_bytes());
+                assert_eq!(ring.verify(&format!("{input}.{signature}")), None, "{alg}");
+            }
+        }
+
+        #[test]
+        fn retired_kid_rejected() {
+            let token = keyring("k1 secret").sign(MSG);
+            let retired = (Utc::now() - hour()).to_rfc3339();
+            let retiring = (Utc::now() + hour()).to_rfc3339();
+
+            assert_eq!(keyring(&format!("k2 new;k1 secret {retired}")).verify(&token), None);
+            assert_eq!(keyring(&format!("k2 new;k1 secret {retiring}")).verify(&token).as_deref(), Some(MSG));
+        }
+
+        #[test]
+        fn signing_key_cannot_retire() {
+            let retiring = (Utc::now() + hour()).to_rfc3339();
+            assert!(Keyring::parse(&format!("k1 secret {retiring}"), None).is_err());
+            assert!(Keyring::parse(&format!("k2 new;k1 secret {retiring}"), Some("k1")).is_err());
+            assert!(Keyring::parse(&format!("k2 new;k1 secret {retiring}"), None).is_ok());
+        }
+
+        #[test]
+        fn legacy_format_cutoff() {
+            let legacy = format!("k1.{}", sign("secret", MSG));
+
+            assert_eq!(keyring("k1 secret").verify(&legacy).as_deref(), Some(MSG));
+            assert_eq!(keyring("k1 secret").legacy_until(Utc::now() + hour()).verify(&legacy).as_deref(), Some(MSG));
+            assert_eq!(keyring("k1 secret").legacy_until(Utc::now() - hour()).verify(&legacy), None);
+            assert_eq!(keyring("k1 other").verify(&legacy), None);
+        }
+
+        #[test]
+        fn seal_bound_to_purpose() {
+            let ring = keyring("k1 secret");
+            let sealed = ring.seal("cursor:/orders", MSG);
+
+            assert_eq!(ring.unseal("cursor:/orders", &sealed).as_deref(), Some(MSG));
+            assert_eq!(ring.unseal("cursor:/sales", &sealed), None);
+            assert_eq!(ring.verify(&sealed), None);
+            assert_eq!(ring.unseal("cursor:/orders", &ring.sign(MSG)), None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(issued_at: Date, ttl: TimeDelta) -> Token {
+        Token {
+            sub: "1".into(),
+            user_id: UserId(1),
+            name: "name".into(),
+            phone: "0800".into(),
+            role: Role::Admin,
+            role_data: Value::Null,
+            metadata: Value::Null,
+            created_at: issued_at,
+            updated_at: issued_at,
+            verified_at: None,
+            issued_at,
+            not_before: issued_at,
+            expires_at: issued_at + ttl,
+        }
+    }
+
+    #[test]
+    fn valid_within_lifetime() {
+        let now = Utc::now();
+        assert!(token(now, DEFAULT_TTL).validate_time(now).is_ok());
+        assert!(token(now, DEFAULT_TTL).validate_time(now + DEFAULT_TTL - TimeDelta::seconds(1)).is_ok());
+    }
+
+    #[test]
+    fn expiry_with_leeway() {
+        let now = Utc::now();
+        let token = token(now, DEFAULT_TTL);
+
+        assert!(token.validate_time(now + DEFAULT_TTL + LEEWAY - TimeDelta::seconds(1)).is_ok());
+        assert!(matches!(token.validate_time(now + DEFAULT_TTL + LEEWAY), Err(Error::TokenExpired)));
+    }
+
+    #[test]
+    fn not_before_with_leeway() {
+        let now = Utc::now();
+        let mut token = token(now, DEFAULT_TTL);
+        token.not_before = now + TimeDelta::minutes(5);
+
+        assert!(matches!(token.validate_time(now), Err(Error::InvalidToken)));
+        assert!(token.validate_time(token.not_before - LEEWAY).is_ok());
+    }
+
+    #[test]
+    fn issued_in_future_rejected() {
+        let now = Utc::now();
+        let token = token(now + LEEWAY + TimeDelta::seconds(1), DEFAULT_TTL);
+
+        assert!(matches!(token.validate_time(now), Err(Error::InvalidToken)));
+        assert!(token.validate_time(now + TimeDelta::seconds(1)).is_ok());
+    }
+
+    #[test]
+    fn from_token_str_checks_time() {
+        let ring = sign::Keyring::parse("k1 secret", None).unwrap();
+        let valid = ring.sign(&serde_json::to_string(&token(Utc::now(), DEFAULT_TTL)).unwrap());
+        let expired = ring.sign(&serde_json::to_string(&token(Utc::now() - TimeDelta::days(1), DEFAULT_TTL)).unwrap());
+
+        assert!(Token::from_token_str(&ring, &valid).is_ok());
+        assert!(matches!(Token::from_token_str(&ring, &expired), Err(Error::TokenExpired)));
+    }
 }
@@ -1,30 +1,54 @@
-use std::{borrow::Cow, env::var, fmt::{Debug, Display, Formatter as Fmt, Result as FmtRes}, future::Future, sync::LazyLock};
-use auth::{Error as AuthError, Role, Token};
+use std::{fmt::{Debug, Display, Formatter as Fmt, Result as FmtRes}, future::Future};
+use auth::{sign::keyring, Error as AuthError, Role, Token};
 use bytes::Bytes;
 use http_body_util::{BodyExt as _, Full};
 use hyper::{header::{AUTHORIZATION, CONTENT_TYPE, COOKIE}, http::response::Builder as ResponseBuilder, Method, StatusCode};
 use serde::{de::DeserializeOwned, Serialize};
+use types::FieldError;
 
 pub use hyper::{body::Incoming as Body, http::request::Parts};
+pub use query::{parse_query, CursorPaginated, CursorPagination, Links, Paginated, Pagination, Paging, Query};
+pub use router::{delete, get, patch, post, put, Extract, Handler, MethodRouter, Params, Path, Req, Router, Session};
+
+pub mod middleware;
+mod query;
+mod router;
 pub use serde_json::{json, ser};
 
 pub type Request = hyper::Request<Body>;
 pub type Response<T = Full<Bytes>> = hyper::Response<T>;
 pub type Result<T = Response, E = Error> = std::result::Result<T,E>;
-pub type Paginate = (u32,u32);
 
 pub const NOT_FOUND: Result = Err(Error::Http(StatusCode::NOT_FOUND));
 pub const UNAUTHORIZED: Result = Err(Error::Auth(AuthError::Unauthorized));
 pub const GET: &Method = &Method::GET;
 pub const POST: &Method = &Method::POST;
+pub const PUT: &Method = &Method::PUT;
+pub const PATCH: &Method = &Method::PATCH;
+pub const DELETE: &Method = &Method::DELETE;
 
 pub enum LogicError {
-    UserIdNotFound(i32)
+    UserIdNotFound(i32),
+    WhIdNotFound(i32),
+    WhDeactivated(i32),
+    OrderNotAvailable(i32),
+    OrderNotCarried(i32),
+    RoleMismatch(i32, Role),
+    NotEmployee(i32),
+    NotAssigned(i32),
+    SameWarehouse(i32),
+    TariffNotFound(String),
+    TariffImmutable(String),
+    ManifestIdNotFound(i32),
+    ManifestCompleted(i32),
+    ManifestWrongWarehouse(i32),
 }
 
 pub enum Error {
     Http(StatusCode),
+    MethodNotAllowed(Vec<Method>),
     BadRequest(String),
+    Validation(Vec<FieldError>),
     InternalError(String),
     Auth(AuthError),
     Logic(LogicError),
@@ -46,10 +70,11 @@ impl Error {
     #[inline]
     pub fn status(&self) -> StatusCode {
         match self {
-            Error::Http(st) => st.clone(),
-            Error::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Error::Http(st) => *st,
+            Error::MethodNotAllowed(_) => StatusCode::METHOD_NOT_ALLOWED,
+            Error::BadRequest(_) | Error::Validation(_) => StatusCode::BAD_REQUEST,
             Error::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
-            Error::Auth(AuthError::Forbidden) => StatusCode::FORBIDDEN,
+            Error::Auth(AuthError::Forbidden | AuthError::NoRoleData | AuthError::Deactivated) => StatusCode::FORBIDDEN,
             Error::Auth(_) => StatusCode::UNAUTHORIZED,
             Error::Logic(_) => StatusCode::UNPROCESSABLE_ENTITY,
         }
@@ -59,7 +84,8 @@ impl Error {
     pub const fn error(&self) -> &'static str {
         match self {
             Error::Http(s) => status_msg(s),
-            Error::BadRequest(_) => "Bad Request",
+            Error::MethodNotAllowed(_) => status_msg(&StatusCode::METHOD_NOT_ALLOWED),
+            Error::BadRequest(_) | Error::Validation(_) => "Bad Request",
             Error::InternalError(_) => "Internal Server Error",
             Error::Auth(er) => er.error(),
             Error::Logic(_) => "Unprocessable Entity",
@@ -71,17 +97,51 @@ impl Error {
     pub fn message(self) -> String {
         match self {
             Error::Http(ref s) => status_msg(s).into(),
+            Error::MethodNotAllowed(ref methods) => format!("Allowed methods: {}", router::allow_header(methods)),
             Error::Auth(er) => er.message().into(),
             Error::BadRequest(m) | Error::InternalError(m) => m,
+            Error::Validation(fields) => validation_msg(&fields),
             Error::Logic(e) => match e {
                 LogicError::UserIdNotFound(id) => format!("User Id `{id}` Not Found "),
+                LogicError::WhIdNotFound(id) => format!("Warehouse Id `{id}` Not Found"),
+                LogicError::WhDeactivated(id) => format!("Warehouse Id `{id}` is deactivated"),
+                LogicError::OrderNotAvailable(id) => format!("Order Id `{id}` is not available in this warehouse"),
+                LogicError::OrderNotCarried(id) => format!("Order Id `{id}` is not carried by this driver"),
+                LogicError::RoleMismatch(id, role) => format!("User Id `{id}` is not {}", role.as_str()),
+                LogicError::NotEmployee(id) => format!("User Id `{id}` is neither Sales nor Driver"),
+                LogicError::NotAssigned(id) => format!("User Id `{id}` is not assigned to any warehouse"),
+                LogicError::SameWarehouse(id) => format!("Warehouse Id `{id}` is the current warehouse"),
+                LogicError::TariffNotFound(region) => format!("No tariff to `{region}`"),
+                LogicError::TariffImmutable(region) => format!("Tariff to `{region}` is already effective"),
+                LogicError::ManifestIdNotFound(id) => format!("Manifest Id `{id}` Not Found"),
+                LogicError::ManifestCompleted(id) => format!("Manifest Id `{id}` is already completed"),
+                LogicError::ManifestWrongWarehouse(id) => format!("Manifest Id `{id}` is not addressed to this warehouse"),
             },
         }
     }
 
+    /// `Allow` header of [`Error::MethodNotAllowed`]
+    pub fn allow(&self) -> Option<String> {
+        match self {
+            Error::MethodNotAllowed(methods) => Some(router::allow_header(methods)),
+            _ => None,
+        }
+    }
+
+    /// invalid fields of [`Error::Validation`]
+    pub fn fields(&self) -> Option<&[FieldError]> {
+        match self {
+            Error::Validation(fields) => Some(fields),
+            _ => None,
+        }
+    }
+
     pub fn message_write(&self, f: &mut Fmt<'_>) -> std::fmt::Result {
+        if let Error::Validation(fields) = self {
+            return write!(f, "{}", validation_msg(fields));
+        }
         write!(f, "{}", match self {
-            Error::BadRequest(m) | Error::InternalError(m) => &*m,
+            Error::BadRequest(m) | Error::InternalError(m) => m,
             Error::Auth(e) => e.message(),
             e => e.error(),
         })
@@ -90,12 +150,51 @@ impl Error {
 
 pub trait BodyExt {
     fn json<T>(self) -> impl Future<Output = Result<T>> + Send where T: DeserializeOwned;
+    fn text(self) -> impl Future<Output = Result<String>> + Send;
 }
 
 impl BodyExt for Body {
     async fn json<T>(self) -> Result<T> where T: DeserializeOwned {
         serde_json::from_slice(&self.collect().await?.to_bytes()).bad_request()
     }
+    async fn text(self) -> Result<String> {
+        String::from_utf8(self.collect().await?.to_bytes().into()).bad_request()
+    }
+}
+
+pub mod csv {
+    /// rfc4180 records, quoted field may contain comma, newline, and `""` escaped quote
+    pub fn parse(input: &str) -> Vec<Vec<String>> {
+        let mut records = vec![];
+        let mut record = vec![];
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut chars = input.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match (quoted, c) {
+                (true, '"') if chars.peek() == Some(&'"') => { chars.next(); field.push('"'); },
+                (true, '"') => quoted = false,
+                (true, c) => field.push(c),
+                (false, '"') => quoted = true,
+                (false, ',') => record.push(std::mem::take(&mut field)),
+                (false, '\r') => {},
+                (false, '\n') => {
+                    record.push(std::mem::take(&mut field));
+                    records.push(std::mem::take(&mut record));
+                },
+                (false, c) => field.push(c),
+            }
+        }
+
+        if !field.is_empty() || !record.is_empty() {
+            record.push(field);
+            records.push(record);
+        }
+
+        records.retain(|e|!(e.len() == 1 && e[0].trim().is_empty()));
+        records
+    }
 }
 
 pub trait Builder {
@@ -130,12 +229,9 @@ impl<S> IntoResponse for S where S: Serialize {
 }
 
 const SESSION_KEY: &str = "access_token";
-const JWT_SECRET: LazyLock<String> = LazyLock::new(||var("JWT_SECRET").expect("unchecked jwt secret"));
 
 pub trait PartsExt<'r> {
     fn normalize_path(&'r self) -> &'r str;
-    fn normalize_prefix(&'r self, prefix: &'r str) -> &'r str;
-    fn parse_query(&'r self) -> Paginate;
     fn get_cookie(&'r self, key: &str) -> Option<&'r str>;
     fn auth_header(&'r self) -> Option<&'r str>;
     fn get_session(&'r self) -> Result<Token>;
@@ -147,25 +243,11 @@ impl<'r> PartsExt<'r> for Parts {
         self.uri.path().strip_suffix("/").unwrap_or(self.uri.path())
     }
 
-    fn normalize_prefix(&'r self, prefix: &'r str) -> &'r str {
-        let p = self.normalize_path();
-        p.strip_prefix(prefix).unwrap_or(p)
-    }
-
-    fn parse_query(&'r self) -> Paginate {
-        fn par((_, v): (Cow<str>,Cow<str>)) -> Option<u32> { v.parse().ok() }
-        let Some(q) = self.uri.query() else { return (20,0); };
-        let mut qs = form_urlencoded::parse(q.as_bytes());
-        let limit = qs.find(|(k,_)|k=="limit").and_then(par).unwrap_or(20);
-        let page = qs.find(|(k,_)|k=="page").and_then(par).unwrap_or(1);
-        (limit, limit * page.checked_sub(1).unwrap_or(page))
-    }
-
     fn get_cookie(&'r self, key: &str) -> Option<&'r str> {
         self.headers.get(COOKIE)?
-            .to_str().ok()?.split('&')
-            .find(|e|e.starts_with(key))?
-            .split_once('=').map(|e|e.1)
+            .to_str().ok()?.split(';')
+            .filter_map(|e|e.trim().split_once('='))
+            .find(|(k,_)|*k == key).map(|e|e.1)
     }
 
     fn auth_header(&'r self) -> Option<&'r str> {
@@ -174,7 +256,7 @@ impl<'r> PartsExt<'r> for Parts {
     }
 
     fn get_session(&self) -> Result<Token> {
-        match Token::from_token_str(&*JWT_SECRET,
+        match Token::from_token_str(keyring(),
             if let Some(t) = self.get_cookie(SESSION_KEY) { t }
             else if let Some(t) = self.auth_header() { t }
             else { return Err(Error::Auth(AuthError::Unauthorized)); })
@@ -227,11 +309,19 @@ impl<T, E> ErrorExt<T> for std::result::Result<T, E> where E: std::error::Error
     }
 }
 
+fn validation_msg(fields: &[FieldError]) -> String {
+    let fields = fields.iter().map(|e|e.field.as_str()).collect::<Vec<_>>();
+    format!("Invalid field: {}", fields.join(", "))
+}
+
 const fn status_msg(status: &StatusCode) -> &'static str {
     match *status {
         StatusCode::BAD_REQUEST => "Bad Request",
         StatusCode::UNAUTHORIZED => "Unauthorized",
         StatusCode::FORBIDDEN => "Forbidden",
+        StatusCode::NOT_FOUND => "Not Found",
+        StatusCode::METHOD_NOT_ALLOWED => "Method Not Allowed",
+        StatusCode::CONFLICT => "Conflict",
         StatusCode::PAYLOAD_TOO_LARGE => "Payload Too Large",
         StatusCode::UNPROCESSABLE_ENTITY => "Unprocessable Entity",
         StatusCode::INTERNAL_SERVER_ERROR => "Internal Server Error",
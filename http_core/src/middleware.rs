@@ -0,0 +1,332 @@
+use std::{any::Any, future::Future, panic::{catch_unwind, AssertUnwindSafe}, pin::Pin, sync::Arc, task::{Context, Poll}, time::Instant};
+use auth::Role;
+use hyper::header::{HeaderName, HeaderValue};
+use tracing::{error, info, info_span, Instrument};
+use crate::{json, Builder as _, Error, Parts, PartsExt as _, Request, Response, Result};
+
+pub type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+type Endpoint<S> = Arc<dyn Fn(Request, S) -> BoxFuture<Response> + Send + Sync>;
+
+pub const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// cross cutting hook around the whole request
+pub trait Middleware<S>: Send + Sync + 'static {
+    fn call(&self, request: Request, state: S, next: Next<S>) -> BoxFuture<Response>;
+}
+
+/// remaining middleware, then the endpoint
+pub struct Next<S> {
+    layers: Arc<[Arc<dyn Middleware<S>>]>,
+    endpoint: Endpoint<S>,
+    index: usize,
+}
+
+impl<S: 'static> Next<S> {
+    pub fn run(self, request: Request, state: S) -> BoxFuture<Response> {
+        match self.layers.get(self.index).cloned() {
+            Some(layer) => layer.call(request, state, Next { index: self.index + 1, ..self }),
+            None => (self.endpoint)(request, state),
+        }
+    }
+}
+
+/// middleware chain, the first layer is the outermost
+///
+/// ```ignore
+/// Stack::new(api::handle)
+///     .layer(SetRequestId)
+///     .layer(AccessLog)
+///     .layer(CatchPanic)
+/// ```
+pub struct Stack<S> {
+    layers: Arc<[Arc<dyn Middleware<S>>]>,
+    endpoint: Endpoint<S>,
+}
+
+impl<S> Clone for Stack<S> {
+    fn clone(&self) -> Self { Self { layers: self.layers.clone(), endpoint: self.endpoint.clone() } }
+}
+
+impl<S> Stack<S> where S: Send + 'static {
+    pub fn new<F, Fut>(endpoint: F) -> Self
+    where
+        F: Fn(Request, S) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Response> + Send + 'static,
+    {
+        Self { layers: Arc::new([]), endpoint: Arc::new(move |req, state|Box::pin(endpoint(req, state))) }
+    }
+
+    pub fn layer<M: Middleware<S>>(mut self, middleware: M) -> Self {
+        let mut layers = self.layers.to_vec();
+        layers.push(Arc::new(middleware));
+        self.layers = layers.into();
+        self
+    }
+
+    pub fn call(&self, request: Request, state: S) -> BoxFuture<Response> {
+        Next { layers: self.layers.clone(), endpoint: self.endpoint.clone(), index: 0 }.run(request, state)
+    }
+}
+
+/// middleware from async function receiving [`Next`]
+pub struct Around<F>(pub F);
+
+impl<S, F, Fut> Middleware<S> for Around<F>
+where
+    F: Fn(Request, S, Next<S>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Response> + Send + 'static,
+{
+    fn call(&self, request: Request, state: S, next: Next<S>) -> BoxFuture<Response> {
+        Box::pin((self.0)(request, state, next))
+    }
+}
+
+/// modify request before the rest of the chain
+pub struct Before<F>(pub F);
+
+impl<S, F> Middleware<S> for Before<F>
+where
+    S: Send + 'static,
+    F: Fn(&mut Request) + Send + Sync + 'static,
+{
+    fn call(&self, mut request: Request, state: S, next: Next<S>) -> BoxFuture<Response> {
+        (self.0)(&mut request);
+        next.run(request, state)
+    }
+}
+
+/// modify response of the rest of the chain
+pub struct After<F>(pub F);
+
+impl<S, F> Middleware<S> for After<F>
+where
+    S: Send + 'static,
+    F: Fn(&mut Response) + Clone + Send + Sync + 'static,
+{
+    fn call(&self, request: Request, state: S, next: Next<S>) -> BoxFuture<Response> {
+        let f = self.0.clone();
+        Box::pin(async move {
+            let mut response = next.run(request, state).await;
+            f(&mut response);
+            response
+        })
+    }
+}
+
+/// id of current request, available in request extensions
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    fn generate() -> Self {
+        Self(format!("{:016x}", rand::random::<u64>()))
+    }
+}
+
+/// [`RequestId`] from `x-request-id` header or generated, echoed in response header
+pub struct SetRequestId;
+
+impl<S: Send + 'static> Middleware<S> for SetRequestId {
+    fn call(&self, mut request: Request, state: S, next: Next<S>) -> BoxFuture<Response> {
+        let id = request.headers().get(X_REQUEST_ID)
+            .and_then(|e|e.to_str().ok())
+            .filter(|e|!e.is_empty() && e.len() <= 64)
+            .map(|e|RequestId(e.into()))
+            .unwrap_or_else(RequestId::generate);
+        let header = HeaderValue::from_str(&id.0).expect("request id is visible ascii");
+
+        request.headers_mut().insert(X_REQUEST_ID, header.clone());
+        request.extensions_mut().insert(id);
+
+        Box::pin(async move {
+            let mut response = next.run(request, state).await;
+            response.headers_mut().insert(X_REQUEST_ID, header);
+            response
+        })
+    }
+}
+
+/// log method, path, status, and latency of every request
+pub struct AccessLog;
+
+impl<S: Send + 'static> Middleware<S> for AccessLog {
+    fn call(&self, request: Request, state: S, next: Next<S>) -> BoxFuture<Response> {
+        let method = request.method().clone();
+        let path = request.uri().path().to_owned();
+        let request_id = request.extensions().get::<RequestId>().map(|e|e.0.clone()).unwrap_or_default();
+        let span = info_span!("", "{method}{path}");
+
+        Box::pin(async move {
+            let start = Instant::now();
+            let response = next.run(request, state).await;
+            info!(
+                target: "access",
+                request_id,
+                status = response.status().as_u16(),
+                latency_ms = start.elapsed().as_secs_f64() * 1000.0,
+                "{method} {path}",
+            );
+            response
+        }.instrument(span))
+    }
+}
+
+/// panic in the rest of the chain become `500` json error
+pub struct CatchPanic;
+
+impl<S: Send + 'static> Middleware<S> for CatchPanic {
+    fn call(&self, request: Request, state: S, next: Next<S>) -> BoxFuture<Response> {
+        let future = match catch_unwind(AssertUnwindSafe(||next.run(request, state))) {
+            Ok(future) => future,
+            Err(panic) => return Box::pin(async move { panic_response(panic) }),
+        };
+
+        Box::pin(async move {
+            match (CatchUnwind { future }).await {
+                Ok(response) => response,
+                Err(panic) => panic_response(panic),
+            }
+        })
+    }
+}
+
+struct CatchUnwind {
+    future: BoxFuture<Response>,
+}
+
+impl Future for CatchUnwind {
+    type Output = std::result::Result<Response, Box<dyn Any + Send>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let future = self.future.as_mut();
+        match catch_unwind(AssertUnwindSafe(||future.poll(cx))) {
+            Ok(Poll::Pending) => Poll::Pending,
+            Ok(Poll::Ready(response)) => Poll::Ready(Ok(response)),
+            Err(panic) => Poll::Ready(Err(panic)),
+        }
+    }
+}
+
+fn panic_response(panic: Box<dyn Any + Send>) -> Response {
+    let message = panic.downcast_ref::<&str>().copied()
+        .or_else(||panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic");
+    error!(target: "panic", "{message}");
+
+    let err = Error::InternalError(message.into());
+    Response::builder()
+        .status(err.status())
+        .json(json!({ "error": err.error(), "message": err.error() }))
+        .expect("static json")
+}
+
+/// route guard, any valid session
+pub fn authenticated(parts: &Parts) -> Result<()> {
+    parts.get_session().map(|_|())
+}
+
+/// route guard, session of `role`
+pub fn role(role: Role) -> impl Fn(&Parts) -> Result<()> + Clone + Send + Sync + 'static {
+    move |parts|parts.get_session_role(role).map(|_|())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use hyper::{server::conn::http1, service::service_fn};
+    use hyper_util::rt::TokioIo;
+    use serde_json::Value;
+    use tokio::io::{duplex, AsyncReadExt as _, AsyncWriteExt as _};
+    use super::*;
+
+    async fn ok(request: Request, _: ()) -> Response {
+        // request id seen by the endpoint, to compare with the echoed one
+        let id = request.extensions().get::<RequestId>().map(|e|e.0.clone()).unwrap_or_default();
+        Response::builder().body(id.into()).unwrap()
+    }
+
+    fn panic_sync(_: Request, _: ()) -> std::future::Ready<Response> {
+        panic!("sync")
+    }
+
+    async fn panic_poll(_: Request, _: ()) -> Response {
+        panic!("poll")
+    }
+
+    /// raw `(status, x-request-id, body)` of a request served by `stack` over an in memory connection
+    fn send(stack: Stack<()>, headers: &str) -> (u16, Option<String>, String) {
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let raw = runtime.block_on(async move {
+            let (mut client, server) = duplex(4096);
+            let service = service_fn(move |request|{
+                let stack = stack.clone();
+                async move { Ok::<_, Infallible>(stack.call(request, ()).await) }
+            });
+            let connection = tokio::spawn(http1::Builder::new().serve_connection(TokioIo::new(server), service));
+
+            let request = format!("GET / HTTP/1.1\r\nhost: test\r\nconnection: close\r\n{headers}\r\n");
+            client.write_all(request.as_bytes()).await.unwrap();
+            let mut raw = String::new();
+            client.read_to_string(&mut raw).await.unwrap();
+            connection.await.unwrap().unwrap();
+            raw
+        });
+
+        let (head, body) = raw.split_once("\r\n\r\n").unwrap();
+        let mut lines = head.lines();
+        let status = lines.next().unwrap().split(' ').nth(1).unwrap().parse().unwrap();
+        let request_id = lines
+            .filter_map(|e|e.split_once(": "))
+            .find(|(k,_)|k.eq_ignore_ascii_case(X_REQUEST_ID.as_str()))
+            .map(|(_,v)|v.to_owned());
+        (status, request_id, body.to_owned())
+    }
+
+    fn assert_panic_response((status, _, body): (u16, Option<String>, String)) {
+        assert_eq!(status, 500);
+        let body: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["error"], "Internal Server Error");
+    }
+
+    #[test]
+    fn panic_before_future_caught() {
+        assert_panic_response(send(Stack::new(panic_sync).layer(CatchPanic), ""));
+    }
+
+    #[test]
+    fn panic_while_polled_caught() {
+        assert_panic_response(send(Stack::new(panic_poll).layer(CatchPanic), ""));
+    }
+
+    #[test]
+    fn panic_caught_under_outer_layers() {
+        let stack = Stack::new(panic_poll).layer(SetRequestId).layer(AccessLog).layer(CatchPanic);
+        let response = send(stack, "x-request-id: abc\r\n");
+        assert_eq!(response.1.as_deref(), Some("abc"));
+        assert_panic_response(response);
+    }
+
+    #[test]
+    fn request_id_echoed() {
+        let (status, request_id, body) = send(Stack::new(ok).layer(SetRequestId), "x-request-id: client-id-1\r\n");
+        assert_eq!(status, 200);
+        assert_eq!(request_id.as_deref(), Some("client-id-1"));
+        assert_eq!(body, "client-id-1");
+    }
+
+    #[test]
+    fn request_id_generated_when_missing_or_too_long() {
+        let too_long = "a".repeat(65);
+        for headers in [String::new(), format!("x-request-id: {too_long}\r\n")] {
+            let (_, request_id, body) = send(Stack::new(ok).layer(SetRequestId), &headers);
+            let request_id = request_id.unwrap();
+            assert_eq!(request_id.len(), 16);
+            assert_ne!(request_id, too_long);
+            assert_eq!(body, request_id);
+        }
+
+        let max = "a".repeat(64);
+        let (_, request_id, _) = send(Stack::new(ok).layer(SetRequestId), &format!("x-request-id: {max}\r\n"));
+        assert_eq!(request_id, Some(max));
+    }
+}
@@ -0,0 +1,407 @@
+use std::ops::Deref;
+use auth::sign::keyring;
+use hyper::{header::LINK, Uri};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sql::{DEFAULT_LIMIT, MAX_LIMIT};
+use types::FieldError;
+use crate::{Builder as _, Error, Req, Response, Result, router::Extract};
+
+/// query string deserialized with serde, `400` with the offending field
+pub struct Query<T>(pub T);
+
+impl<S, T: DeserializeOwned> Extract<S> for Query<T> {
+    fn extract(req: &Req<S>) -> Result<Self> {
+        parse_query(req.parts.uri.query().unwrap_or_default()).map(Query)
+    }
+}
+
+pub fn parse_query<T: DeserializeOwned>(query: &str) -> Result<T> {
+    let de = serde_urlencoded::Deserializer::new(form_urlencoded::parse(query.as_bytes()));
+    serde_path_to_error::deserialize(de).map_err(|err| {
+        let field = match err.path().to_string() {
+            root if root == "." => "query".into(),
+            field => field,
+        };
+        Error::Validation(vec![FieldError::new(field, err.into_inner().to_string())])
+    })
+}
+
+/// `?page=&limit=`, page start from 1, limit bounded by [`MAX_LIMIT`]
+#[derive(Debug, Clone, Copy)]
+pub struct Pagination {
+    pub page: u32,
+    pub limit: u32,
+}
+
+impl Default for Pagination {
+    fn default() -> Self { Self { page: 1, limit: DEFAULT_LIMIT as u32 } }
+}
+
+/// [`Pagination`], or `?cursor=` instead of `page` for endpoints supporting keyset pagination,
+/// empty cursor start from the first item
+#[derive(Debug, Clone)]
+pub struct CursorPagination {
+    pub page: Pagination,
+    pub cursor: Option<String>,
+}
+
+impl Deref for CursorPagination {
+    type Target = Pagination;
+    fn deref(&self) -> &Pagination { &self.page }
+}
+
+#[derive(Deserialize)]
+struct RawPagination {
+    page: Option<u32>,
+    limit: Option<u32>,
+    cursor: Option<String>,
+}
+
+/// how the endpoint should be read, `K` is the keyset of the last item sent
+#[derive(Debug)]
+pub enum Paging<K> {
+    Offset,
+    Cursor(Option<K>),
+}
+
+impl Pagination {
+    pub fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = vec![];
+
+        if self.page < 1 {
+            errors.push(FieldError::new("page", "page must be at least 1"));
+        }
+        if self.limit < 1 || self.limit > MAX_LIMIT as u32 {
+            errors.push(FieldError::new("limit", format!("limit must be between 1 and {MAX_LIMIT}")));
+        }
+        if (self.page as i64 - 1) * self.limit as i64 > i32::MAX as i64 {
+            errors.push(FieldError::new("page", "page is too large"));
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors),
+        }
+    }
+
+    /// `LIMIT` bind
+    pub fn limit(&self) -> i32 { self.limit as i32 }
+
+    /// `OFFSET` bind
+    pub fn offset(&self) -> i32 { self.page.saturating_sub(1).saturating_mul(self.limit).min(i32::MAX as u32) as i32 }
+
+    /// [`Paginated`] envelope, links also sent as `Link` header
+    pub fn respond<T: Serialize>(&self, uri: &Uri, total: i64, data: Vec<T>) -> Result {
+        let last = (total.max(0) as u64).div_ceil(self.limit as u64).max(1);
+        let links = Links {
+            next: (u64::from(self.page) < last).then(||page_url(uri, self.page + 1, self.limit)),
+            prev: (self.page > 1).then(||page_url(uri, (self.page as u64 - 1).min(last) as u32, self.limit)),
+        };
+
+        let mut response = Response::builder();
+        let link = [(&links.next, "next"), (&links.prev, "prev")].into_iter()
+            .filter_map(|(url, rel)|url.as_ref().map(|url|format!("<{url}>; rel=\"{rel}\"")))
+            .collect::<Vec<_>>();
+        if !link.is_empty() {
+            response = response.header(LINK, link.join(", "));
+        }
+
+        response.json(Paginated { data, total, page: self.page, limit: self.limit, links })
+    }
+}
+
+impl CursorPagination {
+    /// `cursor` decoded, only valid for the endpoint it was issued by
+    pub fn paging<K: DeserializeOwned>(&self, uri: &Uri) -> Result<Paging<K>> {
+        match self.cursor.as_deref() {
+            None => Ok(Paging::Offset),
+            Some("") => Ok(Paging::Cursor(None)),
+            Some(cursor) => keyring().unseal(&cursor_purpose(uri), cursor)
+                .and_then(|e|serde_json::from_str(&e).ok())
+                .map(|key|Paging::Cursor(Some(key)))
+                .ok_or_else(||Error::Validation(vec![FieldError::new("cursor", "invalid cursor")])),
+        }
+    }
+
+    /// [`CursorPaginated`] envelope, `key` is the keyset of an item,
+    /// next cursor only issued when the page is full
+    pub fn respond_cursor<T: Serialize, K: Serialize>(&self, uri: &Uri, data: Vec<T>, key: impl Fn(&T) -> K) -> Result {
+        let next_cursor = data.last()
+            .filter(|_|data.len() >= self.limit as usize)
+            .map(|e|serde_json::to_string(&key(e)))
+            .transpose()?
+            .map(|e|keyring().seal(&cursor_purpose(uri), &e));
+        let links = Links {
+            next: next_cursor.as_ref().map(|e|link_url(uri, &[("cursor", e), ("limit", &self.limit.to_string())])),
+            prev: None,
+        };
+
+        let mut response = Response::builder();
+        if let Some(next) = &links.next {
+            response = response.header(LINK, format!("<{next}>; rel=\"next\""));
+        }
+
+        response.json(CursorPaginated { data, limit: self.limit, next_cursor, links })
+    }
+}
+
+/// standard list response
+#[derive(Debug, Serialize)]
+pub struct Paginated<T> {
+    pub data: Vec<T>,
+    pub total: i64,
+    pub page: u32,
+    pub limit: u32,
+    pub links: Links,
+}
+
+/// keyset list response, no total since counting defeat the purpose
+#[derive(Debug, Serialize)]
+pub struct CursorPaginated<T> {
+    pub data: Vec<T>,
+    pub limit: u32,
+    pub next_cursor: Option<String>,
+    pub links: Links,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Links {
+    pub next: Option<String>,
+    pub prev: Option<String>,
+}
+
+fn page_url(uri: &Uri, page: u32, limit: u32) -> String {
+    link_url(uri, &[("page", &page.to_string()), ("limit", &limit.to_string())])
+}
+
+/// same path and query, with paging parameters replaced by `pairs`
+fn link_url(uri: &Uri, pairs: &[(&str, &str)]) -> String {
+    let mut query = form_urlencoded::Serializer::new(String::new());
+    form_urlencoded::parse(uri.query().unwrap_or_default().as_bytes())
+        .filter(|(k,_)|!matches!(&**k, "page" | "limit" | "cursor"))
+        .for_each(|(k,v)|{ query.append_pair(&k, &v); });
+    query.extend_pairs(pairs);
+    format!("{}?{}", uri.path(), query.finish())
+}
+
+/// cursor sealed per path, cannot be replayed on another list
+fn cursor_purpose(uri: &Uri) -> String {
+    format!("cursor:{}", uri.path().trim_end_matches('/'))
+}
+
+impl RawPagination {
+    fn pagination(&self) -> Result<Pagination> {
+        let default = Pagination::default();
+        let page = Pagination {
+            page: self.page.unwrap_or(default.page),
+            limit: self.limit.unwrap_or(default.limit),
+        };
+        page.validate().map_err(Error::Validation)?;
+        Ok(page)
+    }
+}
+
+impl Pagination {
+    fn from_query(query: &str) -> Result<Self> {
+        let raw = parse_query::<RawPagination>(query)?;
+        if raw.cursor.is_some() {
+            return Err(Error::Validation(vec![FieldError::new("cursor", "cursor is not supported by this endpoint")]));
+        }
+        raw.pagination()
+    }
+}
+
+impl CursorPagination {
+    fn from_query(query: &str) -> Result<Self> {
+        let raw = parse_query::<RawPagination>(query)?;
+        if raw.page.is_some() && raw.cursor.is_some() {
+            return Err(Error::Validation(vec![FieldError::new("cursor", "cursor cannot be combined with page")]));
+        }
+        Ok(CursorPagination { page: raw.pagination()?, cursor: raw.cursor })
+    }
+}
+
+impl<S> Extract<S> for Pagination {
+    fn extract(req: &Req<S>) -> Result<Self> {
+        Self::from_query(req.parts.uri.query().unwrap_or_default())
+    }
+}
+
+impl<S> Extract<S> for CursorPagination {
+    fn extract(req: &Req<S>) -> Result<Self> {
+        Self::from_query(req.parts.uri.query().unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http_body_util::BodyExt as _;
+    use serde_json::Value;
+    use super::*;
+
+    fn json_body(response: Response) -> Value {
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let bytes = runtime.block_on(response.into_body().collect()).unwrap().to_bytes();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    fn link(response: &Response) -> Option<&str> {
+        response.headers().get(LINK).map(|e|e.to_str().unwrap())
+    }
+
+    fn page_err(query: &str) -> Vec<FieldError> {
+        match Pagination::from_query(query) {
+            Err(Error::Validation(fields)) => fields,
+            other => panic!("expected validation error for `{query}`, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn default_page_and_limit() {
+        let page = Pagination::from_query("").unwrap();
+        assert_eq!((page.page, page.limit), (1, DEFAULT_LIMIT as u32));
+        assert_eq!((page.limit(), page.offset()), (DEFAULT_LIMIT, 0));
+
+        let page = Pagination::from_query("page=3&limit=20").unwrap();
+        assert_eq!((page.limit(), page.offset()), (20, 40));
+    }
+
+    #[test]
+    fn page_zero_rejected() {
+        assert_eq!(page_err("page=0")[0].field, "page");
+    }
+
+    #[test]
+    fn limit_out_of_range_rejected() {
+        assert_eq!(page_err("limit=0")[0].field, "limit");
+        assert_eq!(page_err(&format!("limit={}", MAX_LIMIT + 1))[0].field, "limit");
+        assert!(Pagination::from_query(&format!("limit={MAX_LIMIT}")).is_ok());
+    }
+
+    #[test]
+    fn offset_overflow() {
+        assert_eq!(page_err(&format!("page={}&limit={MAX_LIMIT}", u32::MAX))[0].field, "page");
+
+        let page = Pagination { page: u32::MAX, limit: MAX_LIMIT as u32 };
+        assert_eq!(page.offset(), i32::MAX);
+    }
+
+    #[test]
+    fn page_not_a_number() {
+        let err = Pagination::from_query("page=abc").unwrap_err();
+        assert_eq!(err.status(), 400);
+        let Error::Validation(fields) = err else { unreachable!() };
+        assert_eq!(fields[0].field, "page");
+    }
+
+    #[test]
+    fn links_middle_page() {
+        let uri = Uri::from_static("/admin/users?page=2&limit=10");
+        let response = Pagination { page: 2, limit: 10 }.respond(&uri, 25, vec![0; 10]).unwrap();
+        assert_eq!(link(&response), Some("</admin/users?page=3&limit=10>; rel=\"next\", </admin/users?page=1&limit=10>; rel=\"prev\""));
+
+        let body = json_body(response);
+        assert_eq!((body["total"].as_i64(), body["page"].as_u64(), body["limit"].as_u64()), (Some(25), Some(2), Some(10)));
+        assert_eq!(body["links"]["next"], "/admin/users?page=3&limit=10");
+        assert_eq!(body["links"]["prev"], "/admin/users?page=1&limit=10");
+    }
+
+    #[test]
+    fn links_last_page() {
+        let uri = Uri::from_static("/admin/users?page=3&limit=10");
+        let response = Pagination { page: 3, limit: 10 }.respond(&uri, 25, vec![0; 5]).unwrap();
+        assert_eq!(link(&response), Some("</admin/users?page=2&limit=10>; rel=\"prev\""));
+
+        let body = json_body(response);
+        assert_eq!(body["links"]["next"], Value::Null);
+        assert_eq!(body["links"]["prev"], "/admin/users?page=2&limit=10");
+    }
+
+    #[test]
+    fn links_past_the_end() {
+        let uri = Uri::from_static("/admin/users?page=9&limit=10");
+        let body = json_body(Pagination { page: 9, limit: 10 }.respond(&uri, 25, Vec::<i32>::new()).unwrap());
+        assert_eq!(body["links"]["next"], Value::Null);
+        assert_eq!(body["links"]["prev"], "/admin/users?page=3&limit=10");
+
+        let uri = Uri::from_static("/admin/users");
+        let response = Pagination::default().respond(&uri, 0, Vec::<i32>::new()).unwrap();
+        assert_eq!(link(&response), None);
+        assert_eq!(json_body(response)["links"], serde_json::json!({ "next": null, "prev": null }));
+    }
+
+    #[test]
+    fn links_keep_other_query() {
+        let uri = Uri::from_static("/admin/warehouses?wh_type=gateway&page=2&q=a%20b&limit=5");
+        let body = json_body(Pagination { page: 2, limit: 5 }.respond(&uri, 20, vec![0; 5]).unwrap());
+        assert_eq!(body["links"]["next"], "/admin/warehouses?wh_type=gateway&q=a+b&page=3&limit=5");
+        assert_eq!(body["links"]["prev"], "/admin/warehouses?wh_type=gateway&q=a+b&page=1&limit=5");
+    }
+
+    fn init_keyring() {
+        static ENV: std::sync::Once = std::sync::Once::new();
+        ENV.call_once(||if ["JWT_KEYS_FILE", "JWT_KEYS", "JWT_SECRET"].iter().all(|e|std::env::var(e).is_err()) {
+            std::env::set_var("JWT_SECRET", "test-secret");
+        });
+    }
+
+    fn cursor_err(page: &CursorPagination, uri: &Uri) -> Vec<FieldError> {
+        match page.paging::<i32>(uri) {
+            Err(Error::Validation(fields)) => fields,
+            other => panic!("expected validation error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn empty_cursor_from_first_item() {
+        let uri = Uri::from_static("/orders");
+        let page = CursorPagination::from_query("cursor=&limit=2").unwrap();
+        assert!(matches!(page.paging::<i32>(&uri), Ok(Paging::Cursor(None))));
+        assert_eq!(page.limit(), 2);
+
+        let page = CursorPagination::from_query("page=2").unwrap();
+        assert!(matches!(page.paging::<i32>(&uri), Ok(Paging::Offset)));
+    }
+
+    #[test]
+    fn next_cursor_only_on_full_page() {
+        init_keyring();
+        let uri = Uri::from_static("/orders?status=x&cursor=&limit=2");
+        let page = CursorPagination::from_query("cursor=&limit=2").unwrap();
+
+        let response = page.respond_cursor(&uri, vec![1, 2], |e|*e).unwrap();
+        let header = link(&response).unwrap().to_owned();
+        let body = json_body(response);
+        let cursor = body["next_cursor"].as_str().unwrap();
+        assert_eq!(body["links"]["next"], format!("/orders?status=x&cursor={cursor}&limit=2"));
+        assert_eq!(header, format!("</orders?status=x&cursor={cursor}&limit=2>; rel=\"next\""));
+
+        let next = CursorPagination::from_query(&format!("cursor={cursor}&limit=2")).unwrap();
+        assert!(matches!(next.paging::<i32>(&uri), Ok(Paging::Cursor(Some(2)))));
+
+        let response = page.respond_cursor(&uri, vec![3], |e|*e).unwrap();
+        assert_eq!(link(&response), None);
+        let body = json_body(response);
+        assert_eq!(body["next_cursor"], Value::Null);
+        assert_eq!(body["links"]["next"], Value::Null);
+    }
+
+    #[test]
+    fn cursor_bound_to_path() {
+        init_keyring();
+        let page = CursorPagination::from_query("cursor=&limit=1").unwrap();
+        let body = json_body(page.respond_cursor(&Uri::from_static("/orders"), vec![1], |e|*e).unwrap());
+        let next = CursorPagination::from_query(&format!("cursor={}", body["next_cursor"].as_str().unwrap())).unwrap();
+
+        assert!(matches!(next.paging::<i32>(&Uri::from_static("/orders/")), Ok(Paging::Cursor(Some(1)))));
+        assert_eq!(cursor_err(&next, &Uri::from_static("/sales"))[0].field, "cursor");
+        assert_eq!(cursor_err(&CursorPagination::from_query("cursor=garbage").unwrap(), &Uri::from_static("/orders"))[0].field, "cursor");
+    }
+
+    #[test]
+    fn cursor_with_page_rejected() {
+        let Err(Error::Validation(fields)) = CursorPagination::from_query("page=2&cursor=") else { panic!("expected validation error") };
+        assert_eq!(fields[0].field, "cursor");
+        assert_eq!(page_err("cursor=")[0].field, "cursor");
+    }
+}
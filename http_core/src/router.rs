@@ -0,0 +1,411 @@
+use std::{future::Future, pin::Pin, str::FromStr, sync::Arc};
+use auth::{RoleData, Token};
+use hyper::{body::Body as _, header::ALLOW, Method, StatusCode};
+use percent_encoding::percent_decode_str;
+use crate::{Body, Builder as _, Error, Parts, PartsExt as _, Request, Response, Result};
+
+type BoxFuture = Pin<Box<dyn Future<Output = Result> + Send>>;
+type BoxHandler<S> = Arc<dyn Fn(Req<S>) -> BoxFuture + Send + Sync>;
+type Guard = Arc<dyn Fn(&Parts) -> Result<()> + Send + Sync>;
+
+/// request passed to route handler
+pub struct Req<S> {
+    pub parts: Parts,
+    pub body: Body,
+    pub params: Params,
+    pub state: S,
+}
+
+impl<S> Req<S> {
+    /// typed path parameter, `400` if cannot be parsed
+    pub fn param<T: FromStr>(&self, name: &str) -> Result<T> {
+        let Some(value) = self.params.get(name) else {
+            return Err(Error::InternalError(format!("route has no path parameter `{name}`")));
+        };
+        value.parse().map_err(|_|Error::BadRequest(format!("invalid {name} `{value}`")))
+    }
+}
+
+/// captured `{name}` path segments, percent decoded
+#[derive(Debug, Default)]
+pub struct Params(Vec<(Arc<str>, String)>);
+
+impl Params {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.iter().find(|(k,_)|&**k == name).map(|e|e.1.as_str())
+    }
+
+    /// last captured segment, most route only have one
+    pub fn last(&self) -> Option<(&str, &str)> {
+        self.0.last().map(|(k,v)|(&**k, v.as_str()))
+    }
+}
+
+/// value taken from request before handler called
+pub trait Extract<S>: Sized {
+    fn extract(req: &Req<S>) -> Result<Self>;
+}
+
+/// last path parameter, parsed with [`FromStr`]
+pub struct Path<T>(pub T);
+
+impl<S, T: FromStr> Extract<S> for Path<T> {
+    fn extract(req: &Req<S>) -> Result<Self> {
+        let Some((name, value)) = req.params.last() else {
+            return Err(Error::InternalError("route has no path parameter".into()));
+        };
+        value.parse().map(Path).map_err(|_|Error::BadRequest(format!("invalid {name} `{value}`")))
+    }
+}
+
+impl<S> Extract<S> for Token {
+    fn extract(req: &Req<S>) -> Result<Self> {
+        req.parts.get_session()
+    }
+}
+
+/// session with role data, `403` if role mismatch
+pub struct Session<T>(pub Token, pub T);
+
+impl<S, T: RoleData> Extract<S> for Session<T> {
+    fn extract(req: &Req<S>) -> Result<Self> {
+        let (token, data) = req.parts.get_session_role(T::ROLE)?.split::<T>()?;
+        Ok(Session(token, data))
+    }
+}
+
+macro_rules! extract_tuple {
+    ($($t:ident),*) => {
+        impl<S, $($t: Extract<S>),*> Extract<S> for ($($t,)*) {
+            fn extract(req: &Req<S>) -> Result<Self> { Ok(($($t::extract(req)?,)*)) }
+        }
+    };
+}
+
+extract_tuple!(A, B);
+extract_tuple!(A, B, C);
+
+/// async function accepting [`Req`], optionally followed by an [`Extract`]
+///
+/// `M` only distinguish the two forms
+pub trait Handler<S, M>: Clone + Send + Sync + 'static {
+    fn call(self, req: Req<S>) -> impl Future<Output = Result> + Send + 'static;
+}
+
+impl<S, F, Fut> Handler<S, ()> for F
+where
+    S: Send + 'static,
+    F: FnOnce(Req<S>) -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = Result> + Send + 'static,
+{
+    fn call(self, req: Req<S>) -> impl Future<Output = Result> + Send + 'static { self(req) }
+}
+
+impl<S, F, Fut, E> Handler<S, (E,)> for F
+where
+    S: Send + 'static,
+    E: Extract<S> + Send + 'static,
+    F: FnOnce(Req<S>, E) -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = Result> + Send + 'static,
+{
+    async fn call(self, req: Req<S>) -> Result {
+        let extracted = E::extract(&req)?;
+        self(req, extracted).await
+    }
+}
+
+/// handlers of single path by method
+pub struct MethodRouter<S> {
+    handlers: Vec<(Method, BoxHandler<S>)>,
+    body_limit: Option<u64>,
+}
+
+impl<S> Default for MethodRouter<S> {
+    fn default() -> Self { Self { handlers: vec![], body_limit: None } }
+}
+
+macro_rules! method {
+    ($($f:ident $m:ident),*) => {
+        $(
+        pub fn $f<S, M, H: Handler<S, M>>(handler: H) -> MethodRouter<S> {
+            MethodRouter::default().on(Method::$m, handler)
+        }
+        )*
+
+        impl<S> MethodRouter<S> {
+            $(
+            pub fn $f<M, H: Handler<S, M>>(self, handler: H) -> Self { self.on(Method::$m, handler) }
+            )*
+        }
+    };
+}
+
+method!(get GET, post POST, put PUT, patch PATCH, delete DELETE);
+
+impl<S> MethodRouter<S> {
+    pub fn on<M, H: Handler<S, M>>(mut self, method: Method, handler: H) -> Self {
+        self.handlers.retain(|(m,_)|*m != method);
+        self.handlers.push((method, Arc::new(move |req|Box::pin(handler.clone().call(req)))));
+        self
+    }
+
+    /// request body larger than `max` bytes is rejected with `413`,
+    /// instead of [`Router::body_limit`]
+    pub fn body_limit(mut self, max: u64) -> Self {
+        self.body_limit = Some(max);
+        self
+    }
+
+    fn allow(&self) -> Vec<Method> {
+        self.handlers.iter().map(|(m,_)|m.clone()).collect()
+    }
+}
+
+impl<S: Send + 'static> MethodRouter<S> {
+    /// reject every handler of this path unless `guard` pass
+    pub fn guard<G>(self, guard: G) -> Self where G: Fn(&Parts) -> Result<()> + Send + Sync + 'static {
+        self.guard_arc(Arc::new(guard))
+    }
+
+    fn guard_arc(self, guard: Guard) -> Self {
+        let handlers = self.handlers.into_iter().map(|(method, handler)| {
+            let guard = guard.clone();
+            let handler: BoxHandler<S> = Arc::new(move |req: Req<S>| match guard(&req.parts) {
+                Ok(()) => handler(req),
+                Err(err) => Box::pin(async move { Err(err) }),
+            });
+            (method, handler)
+        }).collect();
+        Self { handlers, ..self }
+    }
+}
+
+enum Segment {
+    Static(String),
+    Param(Arc<str>),
+}
+
+struct Route<S> {
+    segments: Vec<Segment>,
+    methods: MethodRouter<S>,
+}
+
+impl<S> Route<S> {
+    fn matches(&self, path: &[&str]) -> Option<Params> {
+        if path.len() != self.segments.len() {
+            return None;
+        }
+
+        let mut params = vec![];
+        for (segment, value) in self.segments.iter().zip(path) {
+            match segment {
+                Segment::Static(s) if s == value => {},
+                Segment::Static(_) => return None,
+                Segment::Param(name) => params.push((name.clone(), percent_decode_str(value).decode_utf8_lossy().into_owned())),
+            }
+        }
+        Some(Params(params))
+    }
+
+    fn pattern(&self) -> String {
+        self.segments.iter().map(|e|match e {
+            Segment::Static(s) => format!("/{s}"),
+            Segment::Param(p) => format!("/{{{p}}}"),
+        }).collect()
+    }
+}
+
+/// path router, `{name}` segment capture a parameter
+///
+/// static segment take precedence over parameter, trailing slash is ignored
+///
+/// ```ignore
+/// Router::new()
+///     .route("/orders", get(list_orders).post(create_order))
+///     .nest("/admin", Router::new().route("/users/{id}", get(find_user)))
+/// ```
+pub struct Router<S> {
+    routes: Vec<Route<S>>,
+    body_limit: Option<u64>,
+}
+
+impl<S> Default for Router<S> {
+    fn default() -> Self { Self { routes: vec![], body_limit: None } }
+}
+
+impl<S> Router<S> where S: Clone + Send + Sync + 'static {
+    pub fn new() -> Self { Self::default() }
+
+    /// merged with previously registered handler of the same path
+    pub fn route(self, path: &str, methods: MethodRouter<S>) -> Self {
+        self.insert(Route { segments: parse_segments(path), methods })
+    }
+
+    /// mount every route of `router` under `prefix`
+    pub fn nest(self, prefix: &str, router: Router<S>) -> Self {
+        router.routes.into_iter().fold(self, |router, route| {
+            let mut segments = parse_segments(prefix);
+            segments.extend(route.segments);
+            router.insert(Route { segments, methods: route.methods })
+        })
+    }
+
+    /// request body limit of route without its own [`MethodRouter::body_limit`],
+    /// only the limit of the router that handle the request apply
+    pub fn body_limit(mut self, max: u64) -> Self {
+        self.body_limit = Some(max);
+        self
+    }
+
+    /// reject every route registered so far unless `guard` pass,
+    /// checked before handler extractors
+    pub fn guard<G>(mut self, guard: G) -> Self where G: Fn(&Parts) -> Result<()> + Send + Sync + 'static {
+        let guard: Guard = Arc::new(guard);
+        for route in &mut self.routes {
+            let methods = std::mem::take(&mut route.methods);
+            route.methods = methods.guard_arc(guard.clone());
+        }
+        self
+    }
+
+    fn insert(mut self, route: Route<S>) -> Self {
+        match self.routes.iter_mut().find(|e|e.pattern() == route.pattern()) {
+            Some(existing) => {
+                for (method, handler) in route.methods.handlers {
+                    existing.methods.handlers.retain(|(m,_)|*m != method);
+                    existing.methods.handlers.push((method, handler));
+                }
+                existing.methods.body_limit = route.methods.body_limit.or(existing.methods.body_limit);
+            },
+            None => self.routes.push(route),
+        }
+        self
+    }
+
+    /// `404` if no path match, `405` with `Allow` if no method match,
+    /// `413` if body exceed the limit, `OPTIONS` is answered with `Allow`
+    pub async fn handle(&self, request: Request, state: S) -> Result {
+        let (parts, body) = request.into_parts();
+
+        let Some((route, params)) = self.find(parts.uri.path()) else {
+            return Err(Error::Http(StatusCode::NOT_FOUND));
+        };
+
+        if parts.method == Method::OPTIONS {
+            return Response::builder()
+                .status(StatusCode::NO_CONTENT)
+                .header(ALLOW, allow_header(&route.methods.allow()))
+                .empty();
+        }
+
+        let Some((_, handler)) = route.methods.handlers.iter().find(|(m,_)|*m == parts.method) else {
+            return Err(Error::MethodNotAllowed(route.methods.allow()));
+        };
+
+        // unknown length, e.g. chunked, is treated as exceeding
+        if let Some(max) = route.methods.body_limit.or(self.body_limit) {
+            if body.size_hint().upper().unwrap_or(u64::MAX) > max {
+                return Err(Error::Http(StatusCode::PAYLOAD_TOO_LARGE));
+            }
+        }
+
+        handler(Req { parts, body, params, state }).await
+    }
+}
+
+impl<S> Router<S> {
+    /// fewest parameter win, static segment is more specific
+    fn find(&self, path: &str) -> Option<(&Route<S>, Params)> {
+        let path = path.split('/').filter(|e|!e.is_empty()).collect::<Vec<_>>();
+        self.routes.iter()
+            .filter_map(|e|e.matches(&path).map(|p|(e,p)))
+            .min_by_key(|(_,p)|p.0.len())
+    }
+}
+
+fn parse_segments(path: &str) -> Vec<Segment> {
+    path.split('/').filter(|e|!e.is_empty())
+        .map(|e|match e.strip_prefix('{').and_then(|e|e.strip_suffix('}')) {
+            Some(name) => Segment::Param(name.into()),
+            None => Segment::Static(e.into()),
+        })
+        .collect()
+}
+
+/// `Allow` header value, `OPTIONS` always included
+pub fn allow_header(methods: &[Method]) -> String {
+    methods.iter().map(Method::as_str).chain(["OPTIONS"]).collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn ok(_: Req<()>) -> Result { Response::builder().empty() }
+
+    fn router() -> Router<()> {
+        Router::new()
+            .route("/orders", get(ok).post(ok))
+            .route("/orders/{id}", get(ok))
+            .route("/orders/tracings", get(ok))
+            .route("/orders/{id}/items/{item}", get(ok))
+            .nest("/admin", Router::new()
+                .route("/users/{id}", get(ok).delete(ok))
+                .route("/users/{id}", patch(ok)))
+    }
+
+    fn find(path: &str) -> Option<(String, Vec<(String, String)>)> {
+        let router = router();
+        router.find(path).map(|(route, params)|(
+            route.pattern(),
+            params.0.into_iter().map(|(k,v)|(k.to_string(), v)).collect(),
+        ))
+    }
+
+    fn pair(k: &str, v: &str) -> (String, String) { (k.into(), v.into()) }
+
+    #[test]
+    fn static_segment_over_param() {
+        assert_eq!(find("/orders/tracings"), Some(("/orders/tracings".into(), vec![])));
+        assert_eq!(find("/orders/12"), Some(("/orders/{id}".into(), vec![pair("id", "12")])));
+    }
+
+    #[test]
+    fn trailing_and_repeated_slash_ignored() {
+        assert_eq!(find("/orders/").map(|e|e.0), Some("/orders".into()));
+        assert_eq!(find("//orders//12/").map(|e|e.0), Some("/orders/{id}".into()));
+        assert_eq!(find("/").map(|e|e.0), None);
+    }
+
+    #[test]
+    fn segment_count_must_match() {
+        assert_eq!(find("/orders/12/items"), None);
+        assert_eq!(find("/orders/12/items/3/extra"), None);
+        assert_eq!(find("/orders/12/items/3"), Some(("/orders/{id}/items/{item}".into(), vec![pair("id", "12"), pair("item", "3")])));
+    }
+
+    #[test]
+    fn params_percent_decoded() {
+        assert_eq!(find("/orders/BT%2012").map(|e|e.1), Some(vec![pair("id", "BT 12")]));
+    }
+
+    #[test]
+    fn nested_under_prefix() {
+        assert_eq!(find("/admin/users/1").map(|e|e.0), Some("/admin/users/{id}".into()));
+        assert_eq!(find("/users/1"), None);
+    }
+
+    #[test]
+    fn same_path_merged() {
+        let router = router();
+        let (route, _) = router.find("/admin/users/1").unwrap();
+        assert_eq!(route.methods.allow(), [Method::GET, Method::DELETE, Method::PATCH]);
+        assert_eq!(router.routes.iter().filter(|e|e.pattern() == "/admin/users/{id}").count(), 1);
+    }
+
+    #[test]
+    fn allow_header_include_options() {
+        assert_eq!(allow_header(&[Method::GET, Method::POST]), "GET, POST, OPTIONS");
+        assert_eq!(allow_header(&[]), "OPTIONS");
+    }
+}
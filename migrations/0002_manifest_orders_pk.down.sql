@@ -0,0 +1,3 @@
+-- Add down migration script here
+alter table manifest_orders drop constraint manifest_orders_pkey;
+alter table manifest_orders add primary key (manifest_id);
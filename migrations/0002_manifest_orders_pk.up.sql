@@ -0,0 +1,5 @@
+-- manifest have many orders, but the old primary key was only on manifest_id
+-- which allowed a single order per manifest, key on both columns instead
+
+alter table manifest_orders drop constraint manifest_orders_pkey;
+alter table manifest_orders add primary key (manifest_id, order_id);
@@ -0,0 +1,20 @@
+-- refresh token family, revoked as a whole when reuse detected
+
+create table sessions (
+  session_id      int generated always as identity primary key,
+  user_id         int not null references users(user_id),
+  created_at      timestamptz not null default now(),
+  refreshed_at    timestamptz,
+  revoked_at      timestamptz
+);
+
+create table refresh_tokens (
+  token_hash      text primary key, -- sha256, raw token is never stored
+  session_id      int not null references sessions(session_id),
+  created_at      timestamptz not null default now(),
+  expires_at      timestamptz not null,
+  used_at         timestamptz
+);
+
+create index sessions_user_id_idx on sessions(user_id);
+create index refresh_tokens_session_id_idx on refresh_tokens(session_id);
@@ -0,0 +1,3 @@
+-- users is never deleted, snapshots and employees still reference it
+
+alter table users add column deactivated_at timestamptz;
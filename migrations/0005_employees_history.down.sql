@@ -0,0 +1,6 @@
+-- Add down migration script here
+delete from employees where ended_at is not null;
+drop index employees_active_idx;
+alter table employees drop column ended_at;
+alter table employees drop column employee_id;
+alter table employees add primary key (user_id, wh_id);
@@ -0,0 +1,17 @@
+-- users is an employee of single warehouse,
+-- previous assignment is kept as history with ended_at
+
+alter table employees drop constraint employees_pkey;
+alter table employees add column employee_id int generated always as identity primary key;
+alter table employees add column ended_at timestamptz;
+
+-- old key allowed more than one warehouse per user, only the newest is kept active
+update employees e set ended_at = now()
+where employee_id <> (
+  select employee_id from employees
+  where user_id = e.user_id
+  order by created_at desc, employee_id desc
+  limit 1
+);
+
+create unique index employees_active_idx on employees(user_id) where ended_at is null;
@@ -0,0 +1,52 @@
+-- public tracking number, order_id is sequential and guessable
+-- orders created before this migration is backfilled in the same format,
+-- origin warehouse and date are taken from its first tracing
+
+alter table orders add column resi text unique;
+
+-- same as `Resi` check digit
+create function pg_temp.luhn_check(digits text) returns int language sql immutable as $$
+  select ((10 - sum(case
+    when t.n % 2 = 1 then case when t.d::int * 2 > 9 then t.d::int * 2 - 9 else t.d::int * 2 end
+    else t.d::int
+  end) % 10) % 10)::int
+  from unnest(string_to_array(reverse(digits), null)) with ordinality as t(d, n)
+$$;
+
+-- 12 digits from gen_random_uuid, strong random unlike random(),
+-- last 15 hex digits of a v4 uuid are all random
+create function pg_temp.random_digits() returns text language sql volatile as $$
+  select lpad((('x' || lpad(right(replace(gen_random_uuid()::text, '-', ''), 15), 16, '0'))::bit(64)::bigint
+    % 1000000000000)::text, 12, '0')
+$$;
+
+do $$
+declare
+  o record;
+  wh text;
+  digits text;
+begin
+  for o in
+    select od.order_id, coalesce((w.data->>'wh_id')::int, 0) as wh_id,
+      coalesce(t.traced_at, now()) at time zone 'utc' as traced_at
+    from orders od
+    left join lateral (
+      select * from tracings where order_id = od.order_id order by traced_at, tracing_id limit 1
+    ) t on true
+    left join wh_snapshot w on w.snapshot_id = t.wh_sid
+  loop
+    wh := o.wh_id::text;
+    loop
+      digits := repeat('0', greatest(0, 4 - length(wh))) || wh || to_char(o.traced_at, 'YYMMDD')
+        || pg_temp.random_digits();
+      begin
+        update orders set resi = 'BT' || digits || pg_temp.luhn_check(digits) where order_id = o.order_id;
+        exit;
+      exception when unique_violation then
+        -- collision, retry with another random part
+      end;
+    end loop;
+  end loop;
+end $$;
+
+alter table orders alter column resi set not null;
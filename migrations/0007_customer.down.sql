@@ -0,0 +1,5 @@
+-- Add down migration script here
+drop table customer_addresses;
+drop index orders_receiver_sid_idx;
+drop index orders_sender_sid_idx;
+drop index users_snapshot_user_id_idx;
@@ -0,0 +1,15 @@
+-- customer orders are found by user_id inside snapshot data
+
+create index users_snapshot_user_id_idx on users_snapshot (((data->>'user_id')::int));
+create index orders_sender_sid_idx on orders(sender_sid);
+create index orders_receiver_sid_idx on orders(receiver_sid);
+
+create table customer_addresses (
+  address_id      int generated always as identity primary key,
+  user_id         int not null references users(user_id),
+  label           text not null,
+  destination     json not null, -- Destination
+  created_at      timestamptz not null default now()
+);
+
+create index customer_addresses_user_id_idx on customer_addresses(user_id);
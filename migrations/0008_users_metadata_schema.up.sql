@@ -0,0 +1,17 @@
+-- metadata is role specific, field values are validated by the app
+-- not valid, existing driver without metadata is kept, but any update of it
+-- must supply metadata, except deactivation
+
+alter table users add constraint users_metadata_schema check (
+  json_typeof(metadata) = 'object' and (
+    role <> 'Driver' or deactivated_at is not null or coalesce(
+      -- missing field is null, which would pass the check
+      json_typeof(metadata->'vehicle_type') = 'string' and
+      json_typeof(metadata->'plate') = 'string' and
+      json_typeof(metadata->'sim_number') = 'string' and
+      json_typeof(metadata->'max_payload_kg') = 'number' and
+      json_typeof(metadata->'max_volume_m3') = 'number',
+      false
+    )
+  )
+) not valid;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+alter table orders drop column price;
+alter table orders drop column service;
+drop table tariffs;
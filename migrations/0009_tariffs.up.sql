@@ -0,0 +1,17 @@
+-- Service: regular, express
+-- price in rupiah
+
+create table tariffs (
+  tariff_id       int generated always as identity primary key,
+  wh_id           int not null references warehouses(wh_id), -- origin
+  provinsi        text not null,
+  kabupaten       text not null,
+  service         text not null,
+  rate_per_kg     bigint not null,
+  min_charge      bigint not null,
+  unique          (wh_id, provinsi, kabupaten, service)
+);
+
+-- orders created before this migration does not have price
+alter table orders add column service text;
+alter table orders add column price bigint;
@@ -0,0 +1,12 @@
+-- Add down migration script here
+alter table orders drop column tariff_id;
+delete from tariffs t where exists (
+  select 1 from tariffs n
+  where n.wh_id = t.wh_id and n.provinsi = t.provinsi and n.kabupaten = t.kabupaten
+    and n.service = t.service and n.effective_from > t.effective_from
+);
+drop index tariffs_version_idx;
+alter table tariffs add constraint tariffs_wh_id_provinsi_kabupaten_service_key
+  unique (wh_id, provinsi, kabupaten, service);
+alter table tariffs drop column effective_from;
+alter table tariffs drop column eta_days;
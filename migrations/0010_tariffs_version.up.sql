@@ -0,0 +1,18 @@
+-- tariffs is versioned by effective_from, a version is immutable once effective
+-- orders keep reference to the tariff it was quoted with
+
+alter table tariffs add column eta_days int not null default 1;
+alter table tariffs add column effective_from date not null default current_date;
+alter table tariffs drop constraint tariffs_wh_id_provinsi_kabupaten_service_key;
+
+-- region names are matched case insensitive, so is the version,
+-- rows only differing in case keep the latest one
+delete from tariffs t where exists (
+  select 1 from tariffs n
+  where n.wh_id = t.wh_id and lower(n.provinsi) = lower(t.provinsi) and lower(n.kabupaten) = lower(t.kabupaten)
+    and n.service = t.service and n.tariff_id > t.tariff_id
+);
+create unique index tariffs_version_idx
+  on tariffs (wh_id, lower(provinsi), lower(kabupaten), service, effective_from);
+
+alter table orders add column tariff_id int references tariffs(tariff_id);
@@ -0,0 +1,20 @@
+-- administrative region reference for orders destination
+-- region names compared case insensitive
+
+create table regions (
+  region_id int generated always as identity primary key,
+  provinsi text not null,
+  kabupaten text not null,
+  kecamatan text not null,
+  kelurahan text not null,
+  kodepos text not null
+);
+
+create unique index regions_hierarchy_idx
+  on regions ((lower(provinsi)), (lower(kabupaten)), (lower(kecamatan)), (lower(kelurahan)));
+
+-- prefix search
+create index regions_kelurahan_idx on regions (lower(kelurahan) text_pattern_ops);
+create index regions_kecamatan_idx on regions (lower(kecamatan) text_pattern_ops);
+create index regions_kabupaten_idx on regions (lower(kabupaten) text_pattern_ops);
+create index regions_kodepos_idx on regions (kodepos text_pattern_ops);
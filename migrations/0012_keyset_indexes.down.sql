@@ -0,0 +1,3 @@
+-- Add down migration script here
+drop index order_status_wh_id_idx;
+drop index tracings_keyset_idx;
@@ -0,0 +1,4 @@
+-- keyset pagination, orders use its primary key
+
+create index tracings_keyset_idx on tracings (traced_at, tracing_id);
+create index order_status_wh_id_idx on order_status (wh_id, order_id);
@@ -0,0 +1,2 @@
+-- Add down migration script here
+alter table warehouses drop column deactivated_at;
@@ -0,0 +1,3 @@
+-- warehouses is never deleted, snapshots, employees, and order_status still reference it
+
+alter table warehouses add column deactivated_at timestamptz;
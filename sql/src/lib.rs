@@ -12,15 +12,16 @@ macro_rules! imtable {
     ($tb:ident,$id:ident) => { table!($tb,$id,$tb); };
     ($tb:ident,$id:ident,$al:ident) => {paste!{
 pub const [<$al:upper _TABLE>]:&str = stringify!($tb);
-pub const [<SELECT_ $al:upper>]:&str = concat!("SELECT * FROM ",stringify!($tb)," LIMIT $1 OFFSET $2");
+pub const [<SELECT_ $al:upper>]:&str = concat!("SELECT * FROM ",stringify!($tb)," ORDER BY ",stringify!($id)," LIMIT $1 OFFSET $2");
+pub const [<COUNT_ $al:upper>]:&str = concat!("SELECT count(*) FROM ",stringify!($tb));
 pub const [<FIND_ $al:upper>]:&str = concat!("SELECT * FROM ",stringify!($tb)," WHERE ",stringify!($id)," = $1");
     }};
 }
 
 macro_rules! select {
-    ($tb:ident,$id:ident) => { select!($tb,$id,$tb); };
-    ($tb:ident,$f:ident,$al:ident) => {paste!{
-pub const [<SELECT_ $tb:upper _BY_ $f:upper>]:&str = concat!("SELECT * FROM ",stringify!($tb)," WHERE ",stringify!($f)," = $1 LIMIT $2 OFFSET $3");
+    ($tb:ident,$f:ident,$id:ident) => {paste!{
+pub const [<SELECT_ $tb:upper _BY_ $f:upper>]:&str = concat!("SELECT * FROM ",stringify!($tb)," WHERE ",stringify!($f)," = $1 ORDER BY ",stringify!($id)," LIMIT $2 OFFSET $3");
+pub const [<COUNT_ $tb:upper _BY_ $f:upper>]:&str = concat!("SELECT count(*) FROM ",stringify!($tb)," WHERE ",stringify!($f)," = $1");
     }};
 }
 
@@ -42,14 +43,17 @@ pub const MAX_LIMIT: i32 = 100;
 pub const DEFAULT_LIMIT: i32 = 10;
 
 table!(users, user_id);
-table!(warehouses, user_id, wh);
+table!(warehouses, wh_id, wh);
 imtable!(orders, order_id);
 imtable!(tracings, tracing_id);
 imtable!(manifests, manifest_id);
+imtable!(tariffs, tariff_id);
 
 table!(manifest_orders, manifest_id);
 
-imtable!(order_status, order_id);
+table!(order_status, order_id);
+table!(sessions, session_id);
+table!(customer_addresses, address_id);
 imtable!(users_snapshot, snapshot_id, users_sn);
 imtable!(wh_snapshot, snapshot_id, wh_sn);
 
@@ -58,37 +62,317 @@ id!(wh_snapshot, snapshot_id, wh_sn);
 
 find!(users, phone);
 find!(warehouses, phone, wh);
-select!(tracings, order_id);
-select!(order_status, wh_id);
+select!(tracings, order_id, tracing_id);
+select!(order_status, wh_id, order_id);
+select!(sessions, user_id, session_id);
+select!(warehouses, wh_type, wh_id);
+select!(customer_addresses, user_id, address_id);
+select!(tariffs, wh_id, tariff_id);
 find!(order_status, wh_id);
 
+pub const SELECT_ORDER_STATUS_IN_WH: &str = concat!(
+    "SELECT os.order_id FROM order_status os ",
+    "JOIN tracings t ON os.tracing_id = t.tracing_id ",
+    "WHERE os.order_id = ANY($1) AND os.wh_id = $2 AND t.status = $3 ",
+    "FOR UPDATE OF os"
+);
+
+/// keyset pagination, NULL key start from the first row
+pub const SELECT_ORDERS_AFTER: &str = "SELECT * FROM orders WHERE order_id > COALESCE($1, 0) ORDER BY order_id LIMIT $2";
+
+/// newest first
+pub const SELECT_TRACINGS_LATEST: &str = "SELECT * FROM tracings ORDER BY traced_at DESC, tracing_id DESC LIMIT $1 OFFSET $2";
+pub const SELECT_TRACINGS_BEFORE: &str = concat!(
+    "SELECT * FROM tracings ",
+    "WHERE (traced_at, tracing_id) < (COALESCE($1, 'infinity'::timestamptz), COALESCE($2, 2147483647)) ",
+    "ORDER BY traced_at DESC, tracing_id DESC LIMIT $3"
+);
+
 pub const SELECT_ORDERS_TRACINGS: &str = concat!(
     "SELECT * FROM order_status os ",
     "LEFT JOIN orders o ON os.order_id = o.order_id ",
     "LEFT JOIN tracings s ON os.tracing_id = s.tracing_id ",
-    "LIMIT $1 OFFSET $2"
+    "ORDER BY os.order_id LIMIT $1 OFFSET $2"
+);
+pub const COUNT_ORDERS_TRACINGS: &str = "SELECT count(*) FROM order_status";
+pub const SELECT_ORDERS_TRACINGS_AFTER: &str = concat!(
+    "SELECT * FROM order_status os ",
+    "LEFT JOIN orders o ON os.order_id = o.order_id ",
+    "LEFT JOIN tracings s ON os.tracing_id = s.tracing_id ",
+    "WHERE os.order_id > COALESCE($1, 0) ORDER BY os.order_id LIMIT $2"
+);
+
+pub const SELECT_ORDERS_BY_WH_ID: &str = concat!(
+    "SELECT o.* FROM order_status os ",
+    "JOIN orders o ON os.order_id = o.order_id ",
+    "WHERE os.wh_id = $1 ORDER BY o.order_id DESC LIMIT $2 OFFSET $3"
+);
+pub const COUNT_ORDERS_BY_WH_ID: &str = "SELECT count(*) FROM order_status WHERE wh_id = $1";
+pub const SELECT_ORDERS_BY_WH_ID_BEFORE: &str = concat!(
+    "SELECT o.* FROM order_status os ",
+    "JOIN orders o ON os.order_id = o.order_id ",
+    "WHERE os.wh_id = $1 AND os.order_id < COALESCE($2, 2147483647) ",
+    "ORDER BY os.order_id DESC LIMIT $3"
+);
+
+pub const SELECT_ORDER_STATUS_BY_DRIVER: &str = concat!(
+    "SELECT os.order_id, t.wh_sid FROM order_status os ",
+    "JOIN tracings t ON os.tracing_id = t.tracing_id ",
+    "JOIN users_snapshot u ON t.subject_sid = u.snapshot_id ",
+    "WHERE os.order_id = ANY($1) AND t.status = $2 AND (u.data->>'user_id')::int = $3 ",
+    "FOR UPDATE OF os"
+);
+
+pub const SELECT_MANIFEST_ORDERS_BY_STATUS: &str = concat!(
+    "SELECT mo.order_id FROM manifest_orders mo ",
+    "JOIN order_status os ON mo.order_id = os.order_id ",
+    "JOIN tracings t ON os.tracing_id = t.tracing_id ",
+    "WHERE mo.manifest_id = $1 AND t.status = $2 ",
+    "FOR UPDATE OF os"
+);
+
+pub const FIND_ORDER_TRACKING: &str = concat!(
+    "SELECT o.order_id, o.destination, s.data->>'name', s.data->>'phone', r.data->>'name', r.data->>'phone' ",
+    "FROM orders o ",
+    "JOIN users_snapshot s ON o.sender_sid = s.snapshot_id ",
+    "JOIN users_snapshot r ON o.receiver_sid = r.snapshot_id ",
+    "WHERE o.resi = $1"
+);
+
+pub const SELECT_TRACKING_BY_ORDER_ID: &str = concat!(
+    "SELECT t.status, t.traced_at, u.data->>'name', w.data->>'wh_name' ",
+    "FROM tracings t ",
+    "JOIN users_snapshot u ON t.subject_sid = u.snapshot_id ",
+    "JOIN wh_snapshot w ON t.wh_sid = w.snapshot_id ",
+    "WHERE t.order_id = $1 ORDER BY t.traced_at, t.tracing_id"
+);
+
+pub const SELECT_ORDERS_BY_CUSTOMER: &str = concat!(
+    "SELECT * FROM orders WHERE ",
+    "sender_sid IN (SELECT snapshot_id FROM users_snapshot WHERE (data->>'user_id')::int = $1) OR ",
+    "receiver_sid IN (SELECT snapshot_id FROM users_snapshot WHERE (data->>'user_id')::int = $1) ",
+    "ORDER BY order_id DESC LIMIT $2 OFFSET $3"
+);
+pub const COUNT_ORDERS_BY_CUSTOMER: &str = concat!(
+    "SELECT count(*) FROM orders WHERE ",
+    "sender_sid IN (SELECT snapshot_id FROM users_snapshot WHERE (data->>'user_id')::int = $1) OR ",
+    "receiver_sid IN (SELECT snapshot_id FROM users_snapshot WHERE (data->>'user_id')::int = $1)"
+);
+pub const SELECT_ORDERS_BY_CUSTOMER_BEFORE: &str = concat!(
+    "SELECT * FROM orders WHERE (",
+    "sender_sid IN (SELECT snapshot_id FROM users_snapshot WHERE (data->>'user_id')::int = $1) OR ",
+    "receiver_sid IN (SELECT snapshot_id FROM users_snapshot WHERE (data->>'user_id')::int = $1)",
+    ") AND order_id < COALESCE($2, 2147483647) ORDER BY order_id DESC LIMIT $3"
+);
+
+pub const FIND_ORDERS_BY_CUSTOMER_RESI: &str = concat!(
+    "SELECT order_id FROM orders WHERE resi = $2 AND (",
+    "sender_sid IN (SELECT snapshot_id FROM users_snapshot WHERE (data->>'user_id')::int = $1) OR ",
+    "receiver_sid IN (SELECT snapshot_id FROM users_snapshot WHERE (data->>'user_id')::int = $1))"
+);
+
+pub const SELECT_MANIFESTS_BY_DRIVER: &str = concat!(
+    "SELECT m.* FROM manifests m ",
+    "JOIN users_snapshot u ON m.driver_sid = u.snapshot_id ",
+    "WHERE (u.data->>'user_id')::int = $1 AND m.completed_at IS NULL ",
+    "ORDER BY m.created_at DESC LIMIT $2 OFFSET $3"
+);
+pub const COUNT_MANIFESTS_BY_DRIVER: &str = concat!(
+    "SELECT count(*) FROM manifests m ",
+    "JOIN users_snapshot u ON m.driver_sid = u.snapshot_id ",
+    "WHERE (u.data->>'user_id')::int = $1 AND m.completed_at IS NULL"
+);
+
+pub const FIND_MANIFESTS_BY_DRIVER: &str = concat!(
+    "SELECT m.* FROM manifests m ",
+    "JOIN users_snapshot u ON m.driver_sid = u.snapshot_id ",
+    "WHERE m.manifest_id = $1 AND (u.data->>'user_id')::int = $2"
+);
+
+pub const SELECT_ORDERS_BY_MANIFEST_ID: &str = concat!(
+    "SELECT o.* FROM manifest_orders mo ",
+    "JOIN orders o ON mo.order_id = o.order_id ",
+    "WHERE mo.manifest_id = $1 ORDER BY o.order_id"
 );
 
-pub const FIND_LATEST_TRACING: &str = concat!(
-    "SELECT * FROM tracings WHERE order_id = $1 ORDER BY traced_at DESC LIMIT 1"
+pub const FIND_TARIFFS_EFFECTIVE: &str = concat!(
+    "SELECT * FROM tariffs WHERE wh_id = $1 ",
+    "AND lower(provinsi) = lower($2) AND lower(kabupaten) = lower($3) AND service = $4 ",
+    "AND effective_from <= current_date ORDER BY effective_from DESC LIMIT 1"
 );
 
+pub const FIND_LATEST_TRACING: &str =
+    "SELECT * FROM tracings WHERE order_id = $1 ORDER BY traced_at DESC LIMIT 1";
+
+pub const FIND_LATEST_USERS_SN: &str = concat!(
+    "SELECT snapshot_id FROM users_snapshot WHERE (data->>'user_id')::int = $1 ",
+    "ORDER BY snapshot_id DESC LIMIT 1"
+);
+pub const FIND_LATEST_WH_SN: &str = concat!(
+    "SELECT snapshot_id FROM wh_snapshot WHERE (data->>'wh_id')::int = $1 ",
+    "ORDER BY snapshot_id DESC LIMIT 1"
+);
+pub const FIND_WH_SN_WH_ID: &str = "SELECT (data->>'wh_id')::int FROM wh_snapshot WHERE snapshot_id = $1";
+pub const FIND_MANIFESTS_FOR_UPDATE: &str = "SELECT * FROM manifests WHERE manifest_id = $1 FOR UPDATE";
+pub const FIND_EMPLOYEES_WH: &str = concat!(
+    "SELECT w.wh_id, w.wh_name, w.wh_type FROM employees e ",
+    "JOIN warehouses w ON e.wh_id = w.wh_id ",
+    "WHERE e.user_id = $1 AND e.ended_at IS NULL LIMIT 1"
+);
+pub const SELECT_EMPLOYEES_BY_USER_ID: &str = concat!(
+    "SELECT * FROM employees WHERE user_id = $1 ",
+    "ORDER BY created_at DESC LIMIT $2 OFFSET $3"
+);
+pub const COUNT_EMPLOYEES_BY_USER_ID: &str = "SELECT count(*) FROM employees WHERE user_id = $1";
+pub const FIND_USERS_ROLE: &str = "SELECT role FROM users WHERE user_id = $1";
+pub const FIND_USERS_FOR_UPDATE: &str = "SELECT * FROM users WHERE user_id = $1 FOR UPDATE";
+pub const LOCK_USERS_ROLE: &str = "SELECT role FROM users WHERE user_id = $1 FOR UPDATE";
+
 pub const INSERT_USERS: &str = concat!("INSERT INTO users(",
     "name,phone,password,role",
     ") VALUES ($1,$2,$3,$4)"
 );
+pub const CREATE_USERS: &str = concat!("INSERT INTO users(",
+    "name,phone,password,role,metadata",
+    ") VALUES ($1,$2,$3,$4,$5::json) RETURNING *"
+);
+pub const UPDATE_USERS: &str = concat!("UPDATE users SET ",
+    "name = COALESCE($2,name), phone = COALESCE($3,phone), password = COALESCE($4,password), ",
+    "role = COALESCE($5,role), metadata = COALESCE($6::json,metadata), updated_at = now() ",
+    "WHERE user_id = $1 RETURNING *"
+);
+pub const DEACTIVATE_USERS: &str = concat!("UPDATE users SET ",
+    "deactivated_at = now(), updated_at = now() ",
+    "WHERE user_id = $1 AND deactivated_at IS NULL RETURNING *"
+);
 pub const INSERT_WH: &str = "INSERT INTO warehouses(wh_name,wh_type) VALUES ($1,$2)";
-pub const INSERT_ORDERS: &str = concat!("INSERT INTO orders(",
-    "sender_sid,receiver_sid,destination,packages",
-    ") VALUES ($1,$2,$3,$4)"
+pub const CREATE_WH: &str = "INSERT INTO warehouses(wh_name,wh_type) VALUES ($1,$2) RETURNING *";
+pub const UPDATE_WH: &str = concat!("UPDATE warehouses SET ",
+    "wh_name = COALESCE($2,wh_name), wh_type = COALESCE($3,wh_type), updated_at = now() ",
+    "WHERE wh_id = $1 RETURNING *"
+);
+pub const DEACTIVATE_WH: &str = concat!("UPDATE warehouses SET ",
+    "deactivated_at = now(), updated_at = now() ",
+    "WHERE wh_id = $1 AND deactivated_at IS NULL RETURNING *"
+);
+/// shared lock, warehouse cannot be deactivated until transaction end
+pub const LOCK_WH_ACTIVE: &str = "SELECT deactivated_at IS NULL FROM warehouses WHERE wh_id = $1 FOR SHARE";
+pub const CREATE_ORDERS: &str = concat!("INSERT INTO orders(",
+    "sender_sid,receiver_sid,destination,packages,resi,service,price,tariff_id",
+    ") VALUES ($1,$2,$3,$4,$5,$6,$7,$8) ON CONFLICT (resi) DO NOTHING RETURNING *"
 );
 pub const INSERT_TRACING: &str = concat!("INSERT INTO tracings(",
     "order_id,subject_sid,wh_sid,status",
     ") VALUES ($1,$2,$3,$4)"
 );
+pub const CREATE_TRACING: &str = concat!("INSERT INTO tracings(",
+    "order_id,subject_sid,wh_sid,status",
+    ") VALUES ($1,$2,$3,$4) RETURNING tracing_id"
+);
 pub const INSERT_USERS_SN: &str = "INSERT INTO users_snapshot(data) VALUES ($1::json)";
 pub const CREATE_USERS_SN: &str = "INSERT INTO users_snapshot(data) VALUES ($1::json) RETURNING snapshot_id";
+pub const SNAPSHOT_USERS: &str = concat!("INSERT INTO users_snapshot(data) ",
+    "SELECT json_build_object('user_id',user_id,'name',name,'phone',phone,'role',role,'metadata',metadata) ",
+    "FROM users WHERE user_id = $1 RETURNING snapshot_id"
+);
 pub const INSERT_WH_SN: &str = "INSERT INTO wh_snapshot(data) VALUES ($1)";
+pub const SNAPSHOT_WH: &str = concat!("INSERT INTO wh_snapshot(data) ",
+    "SELECT json_build_object('wh_id',wh_id,'wh_name',wh_name,'wh_type',wh_type) ",
+    "FROM warehouses WHERE wh_id = $1 RETURNING snapshot_id"
+);
 pub const INSERT_EMPLOYEES: &str = "INSERT INTO employees(user_id,wh_id) VALUES($1,$2)";
+pub const CREATE_EMPLOYEES: &str = "INSERT INTO employees(user_id,wh_id) VALUES($1,$2) RETURNING *";
+pub const END_EMPLOYEES: &str = "UPDATE employees SET ended_at = now() WHERE user_id = $1 AND ended_at IS NULL RETURNING *";
 pub const INSERT_ORDER_STATUS: &str = "INSERT INTO order_status(order_id,tracing_id,wh_id) VALUES($1,$2,$3)";
 
+pub const INSERT_MANIFESTS: &str = concat!("INSERT INTO manifests(",
+    "sales_sid,driver_sid,wh_from_sid,wh_to_sid",
+    ") VALUES ($1,$2,$3,$4) RETURNING *"
+);
+pub const INSERT_MANIFEST_ORDERS: &str = "INSERT INTO manifest_orders(manifest_id,order_id) VALUES($1,$2)";
+pub const COMPLETE_MANIFESTS: &str = "UPDATE manifests SET completed_at = now() WHERE manifest_id = $1 RETURNING *";
+pub const LOCK_OPEN_MANIFESTS_BY_ORDERS: &str = concat!(
+    "SELECT manifest_id FROM manifests WHERE completed_at IS NULL AND manifest_id IN ",
+    "(SELECT manifest_id FROM manifest_orders WHERE order_id = ANY($1)) ",
+    "ORDER BY manifest_id FOR UPDATE"
+);
+/// manifest without any order of status `$2` left
+pub const COMPLETE_MANIFESTS_DELIVERED: &str = concat!(
+    "UPDATE manifests m SET completed_at = now() WHERE m.manifest_id = ANY($1) AND NOT EXISTS (",
+    "SELECT 1 FROM manifest_orders mo ",
+    "JOIN order_status os ON mo.order_id = os.order_id ",
+    "JOIN tracings t ON os.tracing_id = t.tracing_id ",
+    "WHERE mo.manifest_id = m.manifest_id AND t.status = $2)"
+);
+
+pub const CREATE_SESSIONS: &str = "INSERT INTO sessions(user_id) VALUES ($1) RETURNING session_id";
+pub const INSERT_REFRESH_TOKENS: &str = concat!("INSERT INTO refresh_tokens(",
+    "token_hash,session_id,expires_at",
+    ") VALUES ($1,$2,now() + make_interval(secs => $3))"
+);
+pub const FIND_REFRESH_TOKENS: &str = concat!(
+    "SELECT rt.session_id, s.user_id, rt.used_at IS NOT NULL, ",
+    "s.revoked_at IS NOT NULL, rt.expires_at <= now() ",
+    "FROM refresh_tokens rt JOIN sessions s ON rt.session_id = s.session_id ",
+    "WHERE rt.token_hash = $1 FOR UPDATE OF rt, s"
+);
+pub const USE_REFRESH_TOKENS: &str = "UPDATE refresh_tokens SET used_at = now() WHERE token_hash = $1";
+pub const REFRESH_SESSIONS: &str = "UPDATE sessions SET refreshed_at = now() WHERE session_id = $1";
+pub const REVOKE_SESSIONS: &str = "UPDATE sessions SET revoked_at = now() WHERE session_id = $1 AND revoked_at IS NULL";
+pub const REVOKE_SESSIONS_BY_USER_ID: &str = concat!(
+    "UPDATE sessions SET revoked_at = now() ",
+    "WHERE session_id = $1 AND user_id = $2 AND revoked_at IS NULL RETURNING session_id"
+);
+pub const REVOKE_ALL_SESSIONS: &str = "UPDATE sessions SET revoked_at = now() WHERE user_id = $1 AND revoked_at IS NULL";
+pub const REVOKE_SESSIONS_BY_TOKEN: &str = concat!(
+    "UPDATE sessions SET revoked_at = now() WHERE revoked_at IS NULL AND session_id = ",
+    "(SELECT session_id FROM refresh_tokens WHERE token_hash = $1)"
+);
+
+pub const CREATE_CUSTOMER_ADDRESSES: &str = concat!("INSERT INTO customer_addresses(",
+    "user_id,label,destination",
+    ") VALUES ($1,$2,$3::json) RETURNING *"
+);
+pub const DELETE_CUSTOMER_ADDRESSES_BY_USER_ID: &str = "DELETE FROM customer_addresses WHERE address_id = $1 AND user_id = $2";
+
+pub const UPSERT_TARIFFS: &str = concat!("INSERT INTO tariffs(",
+    "wh_id,provinsi,kabupaten,service,rate_per_kg,min_charge,eta_days,effective_from",
+    ") VALUES ($1,$2,$3,$4,$5,$6,$7,$8) ",
+    "ON CONFLICT (wh_id,lower(provinsi),lower(kabupaten),service,effective_from) DO UPDATE SET ",
+    "rate_per_kg = EXCLUDED.rate_per_kg, min_charge = EXCLUDED.min_charge, eta_days = EXCLUDED.eta_days ",
+    "WHERE tariffs.effective_from > current_date RETURNING *"
+);
+/// `$1` row numbers of input already effective
+pub const SELECT_TARIFFS_IMMUTABLE: &str = concat!(
+    "SELECT k.n FROM UNNEST($1::int[],$2::int[],$3::text[],$4::text[],$5::text[],$6::date[]) ",
+    "AS k(n,wh_id,provinsi,kabupaten,service,effective_from) ",
+    "JOIN tariffs t ON t.wh_id = k.wh_id AND lower(t.provinsi) = lower(k.provinsi) ",
+    "AND lower(t.kabupaten) = lower(k.kabupaten) AND t.service = k.service AND t.effective_from = k.effective_from ",
+    "WHERE t.effective_from <= current_date"
+);
+pub const SELECT_WH_IDS: &str = "SELECT wh_id FROM warehouses WHERE wh_id = ANY($1)";
+
+pub const FIND_REGIONS_BY_HIERARCHY: &str = concat!(
+    "SELECT * FROM regions WHERE lower(provinsi) = lower($1) AND lower(kabupaten) = lower($2) ",
+    "AND lower(kecamatan) = lower($3) AND lower(kelurahan) = lower($4)"
+);
+pub const EXISTS_REGIONS: &str = "SELECT EXISTS (SELECT 1 FROM regions)";
+/// `$1` is escaped `LIKE` prefix
+pub const SEARCH_REGIONS: &str = concat!(
+    "SELECT * FROM regions WHERE kodepos LIKE $1 || '%' ",
+    "OR lower(kelurahan) LIKE lower($1) || '%' OR lower(kecamatan) LIKE lower($1) || '%' ",
+    "OR lower(kabupaten) LIKE lower($1) || '%' ",
+    "ORDER BY provinsi, kabupaten, kecamatan, kelurahan LIMIT $2 OFFSET $3"
+);
+pub const COUNT_REGIONS_SEARCH: &str = concat!(
+    "SELECT count(*) FROM regions WHERE kodepos LIKE $1 || '%' ",
+    "OR lower(kelurahan) LIKE lower($1) || '%' OR lower(kecamatan) LIKE lower($1) || '%' ",
+    "OR lower(kabupaten) LIKE lower($1) || '%'"
+);
+pub const UPSERT_REGIONS: &str = concat!("INSERT INTO regions(",
+    "provinsi,kabupaten,kecamatan,kelurahan,kodepos",
+    ") SELECT * FROM UNNEST($1::text[],$2::text[],$3::text[],$4::text[],$5::text[]) ",
+    "ON CONFLICT ((lower(provinsi)),(lower(kabupaten)),(lower(kecamatan)),(lower(kelurahan))) DO UPDATE SET ",
+    "provinsi = EXCLUDED.provinsi, kabupaten = EXCLUDED.kabupaten, kecamatan = EXCLUDED.kecamatan, ",
+    "kelurahan = EXCLUDED.kelurahan, kodepos = EXCLUDED.kodepos"
+);
@@ -3,8 +3,8 @@ use hyper::{server::conn::http1::Builder, service::Service};
 use hyper_util::rt::TokioIo;
 use sqlx::{postgres::PgPoolOptions, PgPool};
 use tokio::{net::TcpListener, runtime::Builder as Tokio, spawn};
-use http_core::{Request, Response};
-use tracing::{error, info, info_span, Instrument};
+use http_core::{middleware::{AccessLog, CatchPanic, SetRequestId, Stack}, Request, Response};
+use tracing::{error, info};
 use tracing_subscriber::{layer::SubscriberExt as _, util::SubscriberInitExt as _, EnvFilter};
 
 const DEFAULT_TRACE: &str = if cfg!(debug_assertions) {
@@ -34,7 +34,7 @@ async fn server() -> Result<(), String> {
 
     let _ = dotenvy::dotenv();
 
-    if let Err(_) = var("JWT_SECRET") { Err("JWT_SECRET: env required".to_string())? }
+    if let Err(err) = auth::sign::Keyring::from_env() { Err(format!("keyring: {err}"))? }
 
     let tcp = {
         let addr = var("HOST").unwrap_or("127.0.0.1".into())
@@ -53,24 +53,27 @@ async fn server() -> Result<(), String> {
         Err(err) => Err(format!("DATABASE_URL: {err}"))?,
     };
 
+    // first layer is the outermost
+    let stack = Stack::new(api::handle)
+        .layer(SetRequestId)
+        .layer(AccessLog)
+        .layer(CatchPanic);
+
     loop {
         let Ok((io, _)) = tcp.accept().await else { continue };
-        spawn(Builder::new().serve_connection(TokioIo::new(io), Server(state.clone())));
+        spawn(Builder::new().serve_connection(TokioIo::new(io), Server(state.clone(), stack.clone())));
     }
 }
 
-pub struct Server(PgPool);
+pub struct Server(PgPool, Stack<PgPool>);
 
 impl Service<Request> for Server {
     type Response = Response;
     type Error = hyper::Error;
     type Future = Pin<Box<dyn Future<Output = hyper::Result<Response>> + Send>>;
     fn call(&self, req: Request) -> Self::Future {
-        let span = info_span!("","{}{}",req.method(),req.uri().path());
-        Box::pin(router(req, self.0.clone()).instrument(span))
+        let response = self.1.call(req, self.0.clone());
+        Box::pin(async move { Ok(response.await) })
     }
 }
 
-#[inline(always)]
-async fn router(req: Request, state: PgPool) -> hyper::Result<Response> { Ok(api::handle(req, state).await) }
-
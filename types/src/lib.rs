@@ -1,18 +1,25 @@
 use chrono::{DateTime, Utc};
+use std::fmt::{Display, Formatter, Result as FmtResult};
 use derives::{EnumDecode, EnumExt, FromRow, IdDecode};
 use serde_json::Value;
 
 macro_rules! id {
     ($n:tt) => {
-        #[derive(Debug, Serialize, Deserialize, IdDecode)]
+        #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, IdDecode)]
         pub struct $n(pub i32);
+
+        impl std::str::FromStr for $n {
+            type Err = std::num::ParseIntError;
+            fn from_str(s: &str) -> Result<Self, Self::Err> { s.parse().map(Self) }
+        }
     };
 }
 
+pub use chrono::NaiveDate;
 pub use serde::{Serialize, Deserialize};
 pub type Date = DateTime<Utc>;
 
-#[derive(Debug, PartialEq, Serialize, Deserialize, EnumExt, EnumDecode)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, EnumExt, EnumDecode)]
 pub enum Role {
     Admin,
     Customer,
@@ -34,6 +41,24 @@ pub enum Status {
     Completed,
 }
 
+impl Status {
+    /// human readable tracking step
+    pub fn step(&self) -> &'static str {
+        match self {
+            Status::Warehouse => "At Warehouse",
+            Status::Driver => "In Transit",
+            Status::Completed => "Delivered",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, EnumExt, EnumDecode)]
+pub enum Service {
+    #[default]
+    Regular,
+    Express,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UserAnon {
     pub user_id: Option<UserId>,
@@ -41,7 +66,93 @@ pub struct UserAnon {
     pub phone: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, EnumExt)]
+pub enum VehicleType {
+    Motorcycle,
+    Car,
+    Van,
+    Truck,
+}
+
+/// invalid field of user provided value
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { field: field.into(), message: message.into() }
+    }
+}
+
+/// driver specific data stored in `users.metadata`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DriverMetadata {
+    pub vehicle_type: VehicleType,
+    /// police number, e.g. `B 1234 XYZ`
+    pub plate: String,
+    /// driving license (SIM) number
+    pub sim_number: String,
+    pub max_payload_kg: f32,
+    pub max_volume_m3: f32,
+}
+
+impl DriverMetadata {
+    pub fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = vec![];
+
+        if !valid_plate(&self.plate) {
+            errors.push(FieldError::new("plate", "plate must be 1-2 letters, 1-4 digits, and 0-3 letters"));
+        }
+
+        let sim = self.sim_number.chars().filter(|e|!matches!(e, ' ' | '-')).collect::<String>();
+        if !(12..=16).contains(&sim.len()) || !sim.bytes().all(|e|e.is_ascii_digit()) {
+            errors.push(FieldError::new("sim_number", "sim_number must be 12-16 digits"));
+        }
+
+        if !self.max_payload_kg.is_finite() || self.max_payload_kg <= 0.0 {
+            errors.push(FieldError::new("max_payload_kg", "max_payload_kg must be positive"));
+        }
+
+        if !self.max_volume_m3.is_finite() || self.max_volume_m3 <= 0.0 {
+            errors.push(FieldError::new("max_volume_m3", "max_volume_m3 must be positive"));
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors),
+        }
+    }
+}
+
+/// indonesian police number, spaces are optional
+fn valid_plate(plate: &str) -> bool {
+    let plate = plate.chars().filter(|e|!e.is_whitespace()).collect::<Vec<_>>();
+    let region = plate.iter().take_while(|e|e.is_ascii_alphabetic()).count();
+    let number = plate[region..].iter().take_while(|e|e.is_ascii_digit()).count();
+    let suffix = plate[region + number..].iter().take_while(|e|e.is_ascii_alphabetic()).count();
+    (1..=2).contains(&region) && (1..=4).contains(&number) && suffix <= 3
+        && region + number + suffix == plate.len()
+}
+
+/// `users.metadata` schema based on role
+pub fn validate_metadata(role: &Role, metadata: &Value) -> Result<(), Vec<FieldError>> {
+    let prefixed = |errors: Vec<FieldError>|errors.into_iter()
+        .map(|e|FieldError::new(format!("metadata.{}", e.field), e.message)).collect();
+
+    match role {
+        Role::Driver => match DriverMetadata::deserialize(metadata) {
+            Ok(driver) => driver.validate().map_err(prefixed),
+            Err(err) => Err(vec![FieldError::new("metadata", err.to_string())]),
+        },
+        _ if metadata.is_object() => Ok(()),
+        _ => Err(vec![FieldError::new("metadata", "metadata must be an object")]),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Destination {
     pub kelurahan: String,
     pub kecamatan: String,
@@ -51,6 +162,76 @@ pub struct Destination {
     pub detail: String,
 }
 
+impl Destination {
+    /// match against reference [`Regions`], region names replaced with reference spelling
+    pub fn conform(&mut self, region: Option<&Regions>) -> Result<(), Vec<FieldError>> {
+        let Some(region) = region else {
+            return Err(vec![FieldError::new("destination", format!("`{}, {}, {}, {}` is not a known region",
+                self.kelurahan, self.kecamatan, self.kabupaten, self.provinsi))]);
+        };
+
+        if region.kodepos != self.kodepos.trim() {
+            return Err(vec![FieldError::new("destination.kodepos",
+                format!("kodepos of {} is `{}`", region.kelurahan, region.kodepos))]);
+        }
+
+        self.kelurahan.clone_from(&region.kelurahan);
+        self.kecamatan.clone_from(&region.kecamatan);
+        self.kabupaten.clone_from(&region.kabupaten);
+        self.provinsi.clone_from(&region.provinsi);
+        self.kodepos.clone_from(&region.kodepos);
+        Ok(())
+    }
+}
+
+/// region reference row, see `data/regions.csv`
+#[derive(Debug, Deserialize)]
+pub struct RegionInput {
+    pub provinsi: String,
+    pub kabupaten: String,
+    pub kecamatan: String,
+    pub kelurahan: String,
+    pub kodepos: String,
+}
+
+impl RegionInput {
+    pub const FIELDS: [&'static str; 5] = ["provinsi", "kabupaten", "kecamatan", "kelurahan", "kodepos"];
+
+    /// csv record ordered as [`RegionInput::FIELDS`]
+    pub fn from_record(record: &[String]) -> Result<Self, Vec<FieldError>> {
+        if record.len() != Self::FIELDS.len() {
+            return Err(vec![FieldError::new("record", format!("expected {} columns, found {}", Self::FIELDS.len(), record.len()))]);
+        }
+
+        let mut errors = Self::FIELDS.iter().zip(record)
+            .filter(|(_,value)|value.trim().is_empty())
+            .map(|(field,_)|FieldError::new(*field, format!("{field} cannot be empty")))
+            .collect::<Vec<_>>();
+
+        let kodepos = record[4].trim();
+        if !kodepos.is_empty() && (kodepos.len() != 5 || !kodepos.bytes().all(|e|e.is_ascii_digit())) {
+            errors.push(FieldError::new("kodepos", "kodepos must be 5 digits"));
+        }
+
+        match errors.is_empty() {
+            true => Ok(Self {
+                provinsi: record[0].trim().into(),
+                kabupaten: record[1].trim().into(),
+                kecamatan: record[2].trim().into(),
+                kelurahan: record[3].trim().into(),
+                kodepos: kodepos.into(),
+            }),
+            false => Err(errors),
+        }
+    }
+
+    /// case insensitive hierarchy, unique in `regions`
+    pub fn key(&self) -> [String; 4] {
+        [&self.provinsi, &self.kabupaten, &self.kecamatan, &self.kelurahan].map(|e|e.to_lowercase())
+    }
+}
+
+/// weight in kg, dimension in cm
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Package {
     pub name: String,
@@ -60,6 +241,261 @@ pub struct Package {
     pub height: f32,
 }
 
+/// cm3 per kg
+pub const VOLUMETRIC_DIVISOR: f32 = 6000.0;
+/// kg, heavier package is freight
+pub const MAX_PACKAGE_WEIGHT: f32 = 1000.0;
+/// cm, of each side
+pub const MAX_PACKAGE_DIMENSION: f32 = 500.0;
+
+impl Package {
+    pub fn volumetric_weight(&self) -> f32 {
+        self.length * self.width * self.height / VOLUMETRIC_DIVISOR
+    }
+
+    /// the greater of actual and volumetric weight
+    pub fn chargeable_weight(&self) -> f32 {
+        self.weight.max(self.volumetric_weight())
+    }
+
+    pub fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let errors = [
+            ("weight", self.weight, MAX_PACKAGE_WEIGHT),
+            ("length", self.length, MAX_PACKAGE_DIMENSION),
+            ("width", self.width, MAX_PACKAGE_DIMENSION),
+            ("height", self.height, MAX_PACKAGE_DIMENSION),
+        ].into_iter()
+            .filter(|(_, value, max)|!value.is_finite() || *value <= 0.0 || value > max)
+            .map(|(field, _, max)|FieldError::new(field, format!("{field} must be positive and at most {max}")))
+            .collect::<Vec<_>>();
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors),
+        }
+    }
+}
+
+/// price of packages based on [`Tariffs`]
+#[derive(Debug, Serialize)]
+pub struct Quote {
+    pub tariff_id: i32,
+    pub service: Service,
+    pub actual_weight: f32,
+    pub volumetric_weight: f32,
+    /// rounded up to whole kg
+    pub chargeable_weight: f32,
+    pub rate_per_kg: i64,
+    pub min_charge: i64,
+    pub price: i64,
+    pub eta_days: i32,
+}
+
+impl Tariffs {
+    pub fn quote(&self, packages: &[Package]) -> Result<Quote, Vec<FieldError>> {
+        if packages.is_empty() {
+            return Err(vec![FieldError::new("packages", "packages cannot be empty")]);
+        }
+
+        let errors = packages.iter().enumerate()
+            .flat_map(|(i,p)|p.validate().err().unwrap_or_default().into_iter()
+                .map(move |e|FieldError::new(format!("packages[{i}].{}", e.field), e.message)))
+            .collect::<Vec<_>>();
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let chargeable_weight = packages.iter().map(Package::chargeable_weight).sum::<f32>().ceil();
+        let Some(price) = (chargeable_weight as i64).checked_mul(self.rate_per_kg) else {
+            return Err(vec![FieldError::new("packages", "price is too large")]);
+        };
+
+        Ok(Quote {
+            tariff_id: self.tariff_id,
+            service: self.service,
+            actual_weight: packages.iter().map(|e|e.weight).sum(),
+            volumetric_weight: packages.iter().map(Package::volumetric_weight).sum(),
+            chargeable_weight,
+            rate_per_kg: self.rate_per_kg,
+            min_charge: self.min_charge,
+            price: price.max(self.min_charge),
+            eta_days: self.eta_days,
+        })
+    }
+}
+
+/// new tariff version, existing version can only be changed before effective
+#[derive(Debug, Deserialize)]
+pub struct TariffInput {
+    pub wh_id: WhId,
+    pub provinsi: String,
+    pub kabupaten: String,
+    pub service: Service,
+    pub rate_per_kg: i64,
+    pub min_charge: i64,
+    pub eta_days: i32,
+    pub effective_from: NaiveDate,
+}
+
+impl TariffInput {
+    pub const FIELDS: [&'static str; 8] = [
+        "wh_id", "provinsi", "kabupaten", "service",
+        "rate_per_kg", "min_charge", "eta_days", "effective_from",
+    ];
+
+    pub fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = vec![];
+
+        if self.provinsi.trim().is_empty() {
+            errors.push(FieldError::new("provinsi", "provinsi cannot be empty"));
+        }
+        if self.kabupaten.trim().is_empty() {
+            errors.push(FieldError::new("kabupaten", "kabupaten cannot be empty"));
+        }
+        if self.rate_per_kg <= 0 {
+            errors.push(FieldError::new("rate_per_kg", "rate_per_kg must be positive"));
+        }
+        if self.min_charge < 0 {
+            errors.push(FieldError::new("min_charge", "min_charge cannot be negative"));
+        }
+        if self.eta_days < 1 {
+            errors.push(FieldError::new("eta_days", "eta_days must be at least 1"));
+        }
+        if self.effective_from < Utc::now().date_naive() {
+            errors.push(FieldError::new("effective_from", "effective_from cannot be in the past"));
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors),
+        }
+    }
+
+    /// csv record ordered as [`TariffInput::FIELDS`]
+    pub fn from_record(record: &[String]) -> Result<Self, Vec<FieldError>> {
+        fn parse<T: std::str::FromStr>(field: &str, value: &str, errors: &mut Vec<FieldError>) -> Option<T> {
+            let parsed = value.trim().parse().ok();
+            if parsed.is_none() {
+                errors.push(FieldError::new(field, format!("invalid {field} `{value}`")));
+            }
+            parsed
+        }
+
+        if record.len() != Self::FIELDS.len() {
+            return Err(vec![FieldError::new("record", format!("expected {} columns, found {}", Self::FIELDS.len(), record.len()))]);
+        }
+
+        let mut errors = vec![];
+        let wh_id = parse("wh_id", &record[0], &mut errors);
+        let service = Service::VARIANTS.iter()
+            .find(|e|e.eq_ignore_ascii_case(record[3].trim()))
+            .and_then(|e|Service::from_str(e).ok());
+        if service.is_none() {
+            errors.push(FieldError::new("service", format!("service must be one of {}", Service::VARIANTS.join(", "))));
+        }
+        let rate_per_kg = parse("rate_per_kg", &record[4], &mut errors);
+        let min_charge = parse("min_charge", &record[5], &mut errors);
+        let eta_days = parse("eta_days", &record[6], &mut errors);
+        let effective_from = parse("effective_from", &record[7], &mut errors);
+
+        match (wh_id, service, rate_per_kg, min_charge, eta_days, effective_from) {
+            (Some(wh_id), Some(service), Some(rate_per_kg), Some(min_charge), Some(eta_days), Some(effective_from)) => {
+                let input = Self {
+                    wh_id: WhId(wh_id),
+                    provinsi: record[1].trim().into(),
+                    kabupaten: record[2].trim().into(),
+                    service, rate_per_kg, min_charge, eta_days, effective_from,
+                };
+                input.validate()?;
+                Ok(input)
+            },
+            _ => Err(errors),
+        }
+    }
+
+    /// version identity, region names compared case insensitive like lookup
+    pub fn key(&self) -> (i32, String, String, &'static str, NaiveDate) {
+        (self.wh_id.0, self.provinsi.to_lowercase(), self.kabupaten.to_lowercase(), self.service.as_str(), self.effective_from)
+    }
+}
+
+/// tracking number (airway bill), `BT` followed by digits:
+/// origin warehouse (4), date `yymmdd` (6), random (12), and luhn check digit (1)
+///
+/// warehouse id beyond 4 digits is written as is
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Resi(String);
+
+impl Resi {
+    pub const PREFIX: &'static str = "BT";
+    /// exclusive bound of the random part, should be drawn from a CSPRNG
+    pub const RANDOM_RANGE: u64 = 1_000_000_000_000;
+    const MIN_DIGITS: usize = 4 + 6 + 12 + 1;
+
+    pub fn new(wh_id: &WhId, random: u64) -> Self {
+        let digits = format!("{:04}{}{:012}", wh_id.0, Utc::now().format("%y%m%d"), random % Self::RANDOM_RANGE);
+        let check = luhn_check(&digits);
+        Self(format!("{}{digits}{check}", Self::PREFIX))
+    }
+
+    /// `None` if format or check digit is invalid
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let prefix = value.get(..Self::PREFIX.len())?;
+        if !prefix.eq_ignore_ascii_case(Self::PREFIX) {
+            return None;
+        }
+        let digits = &value[Self::PREFIX.len()..];
+        if digits.len() < Self::MIN_DIGITS || !digits.bytes().all(|e|e.is_ascii_digit()) {
+            return None;
+        }
+        let (body, check) = digits.split_at(digits.len() - 1);
+        (luhn_check(body).to_string() == check).then(||Self(format!("{}{digits}", Self::PREFIX)))
+    }
+
+    pub fn as_str(&self) -> &str { &self.0 }
+}
+
+/// luhn check digit of ascii digits
+fn luhn_check(digits: &str) -> u32 {
+    let sum: u32 = digits.bytes().rev().enumerate().map(|(i,e)|{
+        let d = (e - b'0') as u32;
+        match i % 2 {
+            0 => if d * 2 > 9 { d * 2 - 9 } else { d * 2 },
+            _ => d,
+        }
+    }).sum();
+    (10 - sum % 10) % 10
+}
+
+impl TryFrom<String> for Resi {
+    type Error = String;
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::parse(&value).ok_or_else(||format!("invalid resi `{value}`"))
+    }
+}
+
+impl std::str::FromStr for Resi {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> { Self::try_from(s.to_owned()) }
+}
+
+impl From<Resi> for String { fn from(value: Resi) -> Self { value.0 } }
+impl Display for Resi { fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult { f.write_str(&self.0) } }
+
+impl sqlx::Type<sqlx::Postgres> for Resi {
+    fn type_info() -> <sqlx::Postgres as sqlx::Database>::TypeInfo {
+        <str as sqlx::Type<sqlx::Postgres>>::type_info()
+    }
+}
+
+impl<'r> sqlx::Decode<'r, sqlx::Postgres> for Resi {
+    fn decode(value: <sqlx::Postgres as sqlx::Database>::ValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        Ok(Self::try_from(<String as sqlx::Decode<sqlx::Postgres>>::decode(value)?)?)
+    }
+}
+
 id!(UserId);
 id!(WhId);
 id!(OrderId);
@@ -67,19 +503,21 @@ id!(TracingId);
 id!(ManifestId);
 id!(UserSid);
 id!(WhSid);
+id!(SessionId);
 
 #[derive(Debug, Serialize, FromRow)]
 pub struct Users {
     pub user_id: UserId,
     pub name: String,
     pub phone: String,
-    #[serde(skip_deserializing)]
+    #[serde(skip_serializing)]
     pub password: String,
     pub role: Role,
     pub metadata: Value,
     pub created_at: Date,
     pub updated_at: Date,
     pub verified_at: Option<Date>,
+    pub deactivated_at: Option<Date>,
 }
 
 #[derive(Debug, Serialize, FromRow)]
@@ -89,15 +527,44 @@ pub struct Warehouses {
     pub wh_type: WhType,
     pub created_at: Date,
     pub updated_at: Date,
+    pub deactivated_at: Option<Date>,
 }
 
 #[derive(Debug, Serialize, FromRow)]
 pub struct Orders {
     pub order_id: OrderId,
-    pub sender: UserSid,
-    pub receiver: UserSid,
+    pub sender_sid: UserSid,
+    pub receiver_sid: UserSid,
     pub destination: String,
     pub packages: String,
+    pub resi: Resi,
+    pub service: Option<Service>,
+    pub price: Option<i64>,
+    pub tariff_id: Option<i32>,
+}
+
+/// rate from origin warehouse to destination region, in rupiah
+#[derive(Debug, Serialize, FromRow)]
+pub struct Tariffs {
+    pub tariff_id: i32,
+    pub wh_id: WhId,
+    pub provinsi: String,
+    pub kabupaten: String,
+    pub service: Service,
+    pub rate_per_kg: i64,
+    pub min_charge: i64,
+    pub eta_days: i32,
+    pub effective_from: NaiveDate,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Regions {
+    pub region_id: i32,
+    pub provinsi: String,
+    pub kabupaten: String,
+    pub kecamatan: String,
+    pub kelurahan: String,
+    pub kodepos: String,
 }
 
 #[derive(Debug, Serialize, FromRow)]
@@ -139,9 +606,29 @@ pub struct WhSnapshot {
 
 #[derive(Debug, Serialize, FromRow)]
 pub struct Employees {
+    pub employee_id: i32,
     pub user_id: UserId,
     pub wh_id: WhId,
     pub created_at: Date,
+    pub ended_at: Option<Date>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Sessions {
+    pub session_id: SessionId,
+    pub user_id: UserId,
+    pub created_at: Date,
+    pub refreshed_at: Option<Date>,
+    pub revoked_at: Option<Date>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct CustomerAddresses {
+    pub address_id: i32,
+    pub user_id: UserId,
+    pub label: String,
+    pub destination: Value, // Destination
+    pub created_at: Date,
 }
 
 #[derive(Debug, Serialize, FromRow)]
@@ -159,3 +646,106 @@ pub struct ManifestOrders {
 
 
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn luhn_check_digit() {
+        assert_eq!(luhn_check("7992739871"), 3);
+        assert_eq!(luhn_check("0"), 0);
+        assert_eq!(luhn_check("18"), 2);
+    }
+
+    #[test]
+    fn generated_resi_parse() {
+        for wh_id in [1, 42, 9999, 123456] {
+            for random in [0, 1, 12345678, Resi::RANDOM_RANGE - 1, u64::MAX] {
+                let resi = Resi::new(&WhId(wh_id), random);
+                assert_eq!(Resi::parse(resi.as_str()), Some(resi));
+            }
+        }
+    }
+
+    #[test]
+    fn parse_normalize_prefix_and_whitespace() {
+        let resi = Resi::new(&WhId(7), 42);
+        let lower = format!(" bt{} ", &resi.as_str()[2..]);
+        assert_eq!(Resi::parse(&lower), Some(resi));
+    }
+
+    #[test]
+    fn parse_reject_invalid() {
+        let resi = Resi::new(&WhId(7), 42);
+        let (body, check) = resi.as_str().split_at(resi.as_str().len() - 1);
+        let wrong = (check.parse::<u32>().unwrap() + 1) % 10;
+
+        assert_eq!(Resi::parse(&format!("{body}{wrong}")), None);
+        assert_eq!(Resi::parse(&format!("XX{}", &resi.as_str()[2..])), None);
+        assert_eq!(Resi::parse(&resi.as_str()[..resi.as_str().len() - 2]), None);
+        assert_eq!(Resi::parse(&format!("{body}a")), None);
+        assert_eq!(Resi::parse("BT"), None);
+        assert_eq!(Resi::parse(""), None);
+    }
+
+    #[test]
+    fn transposed_digits_rejected() {
+        let resi = Resi::new(&WhId(1234), 567812345678);
+        let mut digits = resi.as_str().as_bytes().to_vec();
+        // luhn does not detect `09` and `90`
+        let i = Resi::PREFIX.len() + digits[Resi::PREFIX.len()..].windows(2)
+            .position(|e|e[0] != e[1] && !matches!((e[0], e[1]), (b'0', b'9') | (b'9', b'0')))
+            .unwrap();
+        digits.swap(i, i + 1);
+        assert_eq!(Resi::parse(std::str::from_utf8(&digits).unwrap()), None);
+    }
+
+    fn tariff(rate_per_kg: i64) -> Tariffs {
+        Tariffs {
+            tariff_id: 1,
+            wh_id: WhId(1),
+            provinsi: "Jawa Barat".into(),
+            kabupaten: "Bandung".into(),
+            service: Service::default(),
+            rate_per_kg,
+            min_charge: 10_000,
+            eta_days: 2,
+            effective_from: NaiveDate::default(),
+        }
+    }
+
+    fn package(weight: f32, side: f32) -> Package {
+        Package { name: "box".into(), weight, length: side, width: side, height: side }
+    }
+
+    #[test]
+    fn quote_chargeable_weight() {
+        let quote = tariff(5_000).quote(&[package(1.2, 10.0), package(0.5, 30.0)]).unwrap();
+        assert_eq!(quote.chargeable_weight, 6.0);
+        assert_eq!(quote.price, 30_000);
+        assert_eq!(tariff(5_000).quote(&[package(0.1, 1.0)]).unwrap().price, 10_000);
+    }
+
+    #[test]
+    fn quote_reject_out_of_range_package() {
+        for package in [package(1e20, 10.0), package(1.0, 1e20), package(f32::NAN, 10.0), package(0.0, 10.0)] {
+            assert!(tariff(5_000).quote(&[package]).is_err());
+        }
+        assert!(tariff(5_000).quote(&[package(MAX_PACKAGE_WEIGHT, MAX_PACKAGE_DIMENSION)]).is_ok());
+    }
+
+    #[test]
+    fn quote_reject_overflow() {
+        let errors = tariff(i64::MAX).quote(&[package(2.0, 10.0)]).unwrap_err();
+        assert_eq!(errors[0].field, "packages");
+    }
+
+    #[test]
+    fn backfilled_resi_parse() {
+        // generated by migration 0006
+        for resi in ["BT00072503048925626523219", "BT123452412313428837188585", "BT00002610183554470163352"] {
+            assert!(Resi::parse(resi).is_some(), "{resi}");
+        }
+    }
+}